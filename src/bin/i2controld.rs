@@ -0,0 +1,191 @@
+//! Daemon which owns the connection to the mouse and serves requests over a Unix socket.
+//!
+//! Requests and responses are JSON-RPC 2.0 objects, one per line. Supported methods:
+//! - `get`: returns the current settings, in the same schema as `i2control export`. Only built with
+//!   the `unverified-reads` feature, since the read requests haven't been captured yet
+//! - `apply`: sends the settings given as params, in the same schema as `i2control apply`
+//! - `battery`: returns the battery percentage and charging state. Only built with the
+//!   `unverified-reads` feature, since the battery request hasn't been captured yet
+
+use std::{
+    env,
+    ffi::CString,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+};
+
+use clap::{ArgAction, Parser};
+use hidapi::HidApi;
+use log::{info, warn};
+
+use i2control::{
+    Error,
+    cache::DpiCache,
+    device::{
+        discovery::{find_devices, select_device},
+        transport::Transport,
+    },
+    rpc::{Device, respond},
+    util::logging,
+};
+
+/// Name of the socket within the runtime directory.
+const SOCKET_NAME: &str = "i2controld.sock";
+
+/// Path of the socket which [`remove_socket`] removes.
+static SOCKET_PATH: OnceLock<CString> = OnceLock::new();
+
+/// Daemon arguments
+#[derive(Debug, Parser)]
+#[command(
+    name = "i2controld",
+    about = "Daemon sharing a single connection to a Model I2 Wireless"
+)]
+struct Args {
+    /// Path of the socket to listen on.
+    ///
+    /// Defaults to i2controld.sock in $XDG_RUNTIME_DIR, or in /tmp if that isn't set.
+    #[arg(long = "socket")]
+    socket: Option<PathBuf>,
+
+    /// Index of the device to use, as shown by `i2control list`.
+    ///
+    /// Only required when more than one supported device is connected.
+    #[arg(long = "device")]
+    device: Option<usize>,
+
+    /// Log more detail.
+    ///
+    /// `-v` logs debug messages, and `-vv` also traces every report sent to and received from the
+    /// mouse. RUST_LOG overrides this if set.
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Only log errors.
+    #[arg(short = 'q', long = "quiet")]
+    quiet: bool,
+}
+
+/// Answers each request sent by a client until it disconnects.
+fn serve<T: Transport, F: FnMut() -> i2control::Result<T>>(
+    stream: UnixStream,
+    device: &Mutex<Device<T, F>>,
+) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = respond(device, &line) {
+            writeln!(writer, "{response}")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn socket_path(args: &Args) -> PathBuf {
+    args.socket.clone().unwrap_or_else(|| {
+        env::var_os("XDG_RUNTIME_DIR")
+            .map_or_else(env::temp_dir, PathBuf::from)
+            .join(SOCKET_NAME)
+    })
+}
+
+/// Removes the socket and exits when the daemon is stopped.
+///
+/// Only async-signal-safe functions can be called from a signal handler, so the path is unlinked
+/// directly instead of unwinding.
+extern "C" fn remove_socket(_signal: libc::c_int) {
+    if let Some(path) = SOCKET_PATH.get() {
+        unsafe { libc::unlink(path.as_ptr()) };
+    }
+
+    unsafe { libc::_exit(0) };
+}
+
+/// Removes the socket at `path` when the daemon is stopped by SIGINT or SIGTERM.
+fn remove_socket_on_exit(path: &Path) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    SOCKET_PATH.get_or_init(|| path);
+
+    let handler = remove_socket as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    logging::init(logging::level(args.quiet, args.verbose));
+
+    let mut api = HidApi::new().map_err(Error::from)?;
+    let index = args.device;
+    let mut device = Device::new(move || {
+        api.refresh_devices()?;
+        let candidate = select_device(find_devices(&api)?, index)?;
+        let mouse = candidate.open(&api)?;
+        info!("Connected to {candidate}");
+        Ok(mouse)
+    })
+    .with_dpi_cache(DpiCache::from_env());
+
+    // The mouse may be plugged in later, so only warn if it can't be opened yet.
+    if let Err(e) = device.connect() {
+        warn!("Couldn't connect to the mouse yet: {e}");
+    }
+
+    let path = socket_path(&args);
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(
+                format!("Another daemon is already listening on {}", path.display()).into(),
+            );
+        }
+
+        std::fs::remove_file(&path)?; // Left over from a previous run
+    }
+
+    let listener = UnixListener::bind(&path)?;
+    remove_socket_on_exit(&path)?;
+    info!("Listening on {}", path.display());
+
+    let device = Arc::new(Mutex::new(device));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let device = Arc::clone(&device);
+        thread::spawn(move || {
+            if let Err(e) = serve(stream, &device) {
+                info!("Client disconnected: {e}");
+            }
+        });
+    }
+
+    Ok(())
+}
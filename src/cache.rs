@@ -0,0 +1,106 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+
+use crate::device::dpi::DpiSettings;
+
+/// Name of the directory for i2control's files within the state directory.
+const STATE_DIR_NAME: &str = "i2control";
+
+/// Name of the DPI cache file within i2control's state directory.
+const DPI_FILE_NAME: &str = "dpi.toml";
+
+/// The DPI settings which were last sent to the mouse, kept in a TOML file.
+///
+/// Without the `unverified-reads` feature, the mouse's current settings can't be read back, so
+/// these are used instead to keep settings which an update doesn't change. They're out of date if
+/// the settings were changed by something else since, e.g. Glorious Core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpiCache {
+    path: PathBuf,
+    read_only: bool,
+}
+
+impl DpiCache {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            read_only: false,
+        }
+    }
+
+    /// Makes the cache ignore settings which are stored, e.g. for a dry run, where nothing is sent.
+    pub fn read_only(self) -> Self {
+        Self {
+            read_only: true,
+            ..self
+        }
+    }
+
+    /// Returns the cache in `$XDG_STATE_HOME/i2control`, or in `~/.local/state/i2control` if that
+    /// isn't set.
+    ///
+    /// Returns `None` if neither `XDG_STATE_HOME` nor `HOME` is set.
+    pub fn from_env() -> Option<Self> {
+        let state_dir = env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))?;
+
+        Some(Self::new(
+            state_dir.join(STATE_DIR_NAME).join(DPI_FILE_NAME),
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the cached settings, or `None` if nothing has been cached yet.
+    ///
+    /// A cache which can't be read is logged and treated as empty.
+    pub fn load(&self) -> Option<DpiSettings> {
+        let toml = match fs::read_to_string(&self.path) {
+            Ok(toml) => toml,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Couldn't read {}: {e}", self.path.display());
+                return None;
+            }
+        };
+
+        let settings = toml::from_str(&toml)
+            .inspect_err(|e| warn!("Ignoring invalid {}: {e}", self.path.display()))
+            .ok()?;
+        debug!("Loaded cached DPI settings from {}", self.path.display());
+        Some(settings)
+    }
+
+    /// Caches settings which were sent to the mouse.
+    ///
+    /// Failures are only logged, since the settings were still sent.
+    pub fn store(&self, settings: &DpiSettings) {
+        if self.read_only {
+            return;
+        }
+
+        let result = toml::to_string(settings)
+            .map_err(io::Error::other)
+            .and_then(|toml| {
+                if let Some(dir) = self.path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&self.path, toml)
+            });
+
+        if let Err(e) = result {
+            warn!(
+                "Couldn't cache DPI settings in {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
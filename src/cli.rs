@@ -1,17 +1,25 @@
-use clap::{Parser, Subcommand, value_parser};
-use i2control::util::lighting_mode::LightingMode;
+use std::time::Duration;
 
-// TODO: Change this to automatically detect connected devices matching known VID/PIDs.
-//       This may require some sort of udev rule generation, at least on linux targets.
+use clap::{ArgAction, Parser, Subcommand, ValueEnum, value_parser};
+use i2control::{
+    device::{dpi, lighting, timeout},
+    util::{lighting_mode::LightingMode, logging, rgb::Rgb},
+};
+use log::LevelFilter;
 
-/// Model I2 Wireless Vendor ID
-pub const I2_WL_VID: u16 = 0x93A;
-/// Model I2 Wireless 2.4GHz wireless mode Product ID
-pub const I2_WL_PID: u16 = 0x821D;
+/// Parses a positive number of seconds, which may be fractional.
+fn seconds(s: &str) -> Result<Duration, String> {
+    match s.parse::<f32>() {
+        Ok(secs) if secs > 0.0 => Duration::try_from_secs_f32(secs).map_err(|e| e.to_string()),
+        Ok(_) => Err("must be more than 0".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
+/// Value parser accepting the given inclusive range, shared with the library's validation.
 macro_rules! range {
-    ($t:ty, $l:expr, $h:expr) => {
-        value_parser!($t).range($l..=$h)
+    ($t:ty, $r:expr) => {
+        value_parser!($t).range(*$r.start() as i64..=*$r.end() as i64)
     };
 }
 
@@ -22,13 +30,56 @@ macro_rules! range {
     about = "CLI replacement for Glorious Control for the Model I2 Wireless"
 )]
 pub struct Cli {
-    /// Vendor ID
-    #[arg(default_value_t = I2_WL_VID)]
-    pub vid: u16,
+    /// Index of the device to use, as shown by `list`.
+    ///
+    /// Only required when more than one supported device is connected.
+    #[arg(long = "device", global = true)]
+    pub device: Option<usize>,
+
+    /// Vendor ID.
+    ///
+    /// Skips automatic device detection when given together with a product ID.
+    #[arg(long = "vid", global = true, requires = "pid")]
+    pub vid: Option<u16>,
+
+    /// Product ID.
+    ///
+    /// Skips automatic device detection when given together with a vendor ID.
+    #[arg(long = "pid", global = true, requires = "vid")]
+    pub pid: Option<u16>,
+
+    /// Path of a hidraw device (e.g. /dev/hidraw3) to use directly instead of hidapi.
+    #[cfg(target_os = "linux")]
+    #[arg(long = "hidraw", global = true, conflicts_with_all = ["device", "vid"])]
+    pub hidraw: Option<std::path::PathBuf>,
 
-    /// Product ID
-    #[arg(default_value_t = I2_WL_PID)]
-    pub pid: u16,
+    /// Print the reports that would be sent instead of opening a device.
+    ///
+    /// Each report is printed as it's sent, so commands which keep running, like `animate` and
+    /// `rules`, print until interrupted. Reports are printed as hex by default. `--dry-run=capture` prints them as hex dumps padded
+    /// to 64 bytes, in the same format as the captures in `data/`.
+    #[arg(long = "dry-run", global = true, value_enum, value_name = "FORMAT")]
+    #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "hex")]
+    pub dry_run: Option<DumpFormat>,
+
+    /// Format of the output.
+    ///
+    /// JSON output is a single document for each command, or one document per line for commands
+    /// which keep running.
+    #[arg(long = "format", global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Print more detail to stderr.
+    ///
+    /// `-v` prints debug messages, and `-vv` also traces every report sent to and received from the
+    /// mouse. RUST_LOG overrides this if set.
+    #[arg(short = 'v', long = "verbose", global = true, action = ArgAction::Count)]
+    #[arg(conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only print errors to stderr.
+    #[arg(short = 'q', long = "quiet", global = true)]
+    pub quiet: bool,
 
     #[command(subcommand)]
     pub command: Commands,
@@ -37,65 +88,231 @@ pub struct Cli {
 /// Command types
 #[derive(Debug, Subcommand)]
 pub enum Commands {
+    /// List connected supported devices
+    List,
+
+    /// Watch for supported devices being connected or disconnected.
+    ///
+    /// The dongle stays connected while a wireless mouse sleeps, so a mouse waking up isn't
+    /// detected.
+    Watch {
+        /// Config file to apply whenever a device is connected.
+        #[arg(short = 'a', long = "apply")]
+        apply: Option<std::path::PathBuf>,
+
+        /// Time between scans for devices (ms).
+        #[arg(long = "interval", default_value_t = 1000)]
+        interval: u64,
+
+        /// Scan for devices using hidapi instead of sysfs.
+        ///
+        /// Always used on platforms other than Linux.
+        #[arg(long = "hidapi")]
+        hidapi: bool,
+    },
+
     /// Configure RGB lighting settings
     #[command(name = "rgb", short_flag = 'l')]
     Lighting {
         /// Ranges from 0 to 20 (inclusive)
-        #[arg(value_parser = range!(u8, 0x00, 0x14))]
+        #[arg(value_parser = range!(u8, lighting::BRIGHTNESS_RANGE))]
         brightness: u8,
 
         /// Ranges from 1 to 20 (inclusive)
-        #[arg(value_parser = range!(u8, 0x01, 0x14))]
+        #[arg(value_parser = range!(u8, lighting::RATE_RANGE))]
         rate: u8,
 
+        /// Brightness when connected wirelessly, if different.
+        ///
+        /// Ranges from 0 to 20 (inclusive)
+        #[arg(long = "wireless-brightness", value_parser = range!(u8, lighting::BRIGHTNESS_RANGE))]
+        wireless_brightness: Option<u8>,
+
+        /// Rate when connected wirelessly, if different.
+        ///
+        /// Ranges from 1 to 20 (inclusive)
+        #[arg(long = "wireless-rate", value_parser = range!(u8, lighting::RATE_RANGE))]
+        wireless_rate: Option<u8>,
+
+        /// Full palette of the mode, separated by commas, replacing the colours Glorious Core
+        /// sends.
+        ///
+        /// The number of colours must match the mode. The first colour replaces the mode's custom
+        /// colour.
+        #[arg(short = 'c', long = "colours", alias = "colors", value_delimiter = ',')]
+        colours: Vec<Rgb>,
+
         #[command(subcommand)]
         mode: LightingMode,
     },
 
+    /// Play a lighting effect drawn by this program, by sending a single colour for each frame.
+    ///
+    /// The mouse needs time to process each report, so frames are limited to about two per
+    /// second, whatever the frame rate. Runs until the effect finishes or is interrupted.
+    Animate {
+        /// Frames per second.
+        #[arg(long = "fps", default_value_t = 2.0)]
+        fps: f32,
+
+        /// Ranges from 0 to 20 (inclusive)
+        #[arg(long = "brightness", default_value_t = *lighting::BRIGHTNESS_RANGE.end())]
+        #[arg(value_parser = range!(u8, lighting::BRIGHTNESS_RANGE))]
+        brightness: u8,
+
+        /// Stop after this many seconds.
+        #[arg(long = "duration", value_parser = seconds)]
+        duration: Option<Duration>,
+
+        #[command(subcommand)]
+        effect: Effect,
+    },
+
+    /// Find out which palette positions of a lighting mode the firmware uses.
+    ///
+    /// For each position, a palette which is black except for that position is sent, and you're
+    /// asked whether the mouse shows the colour. The lighting is restored afterwards, using the
+    /// same guessed request as `get`.
+    #[cfg(feature = "unverified-reads")]
+    ProbePalette {
+        /// Colour to show at each position.
+        #[arg(long = "colour", alias = "color", default_value = "#FFFFFF")]
+        colour: Rgb,
+
+        #[command(subcommand)]
+        mode: LightingMode,
+    },
+
+    /// Apply every setting in a TOML config file.
+    ///
+    /// The whole file is validated before anything is sent. Settings which aren't in the file are
+    /// left unchanged.
+    Apply {
+        /// Path of the config file
+        path: std::path::PathBuf,
+    },
+
+    /// Set the lighting from system state, following the rules in a TOML config file.
+    ///
+    /// Runs until interrupted. See the [rules] section of the config for the available signals.
+    Rules {
+        /// Path of the config file
+        path: std::path::PathBuf,
+    },
+
+    /// Print the current lighting and DPI settings as a TOML config file, which can be used with
+    /// `apply`
+    ///
+    /// Uses the same guessed requests as `get`.
+    #[cfg(feature = "unverified-reads")]
+    Export,
+
+    /// Read the current lighting and DPI settings from the mouse
+    ///
+    /// The read requests are guesses, which haven't been checked against a capture of Glorious
+    /// Core.
+    #[cfg(feature = "unverified-reads")]
+    #[command(short_flag = 'g')]
+    Get {
+        #[arg(value_enum, default_value_t = Setting::All)]
+        setting: Setting,
+    },
+
     /// Get the current battery percentage
+    ///
+    /// The battery request is a guess, which hasn't been checked against a capture of Glorious
+    /// Core. It's only sent when built with the unverified-reads feature, and the command fails
+    /// otherwise.
     #[command(short_flag = 'b')]
-    Battery {},
+    Battery {
+        /// Print the percentage and charging state in a machine-readable format.
+        ///
+        /// Prints the percentage followed by either "charging" or "discharging", e.g.
+        /// "85 charging".
+        #[arg(short = 'm', long = "machine")]
+        machine: bool,
+
+        #[command(flatten)]
+        watch: BatteryWatch,
+    },
+
+    /// Serve the battery, connection mode, DPI and polling rate as OpenMetrics over HTTP.
+    ///
+    /// Metrics are read from the mouse on each request to /metrics, using the same guessed
+    /// requests as `battery` and `get`.
+    #[cfg(feature = "unverified-reads")]
+    ServeMetrics {
+        /// Address to listen on.
+        #[arg(long = "listen", default_value = "127.0.0.1:9750")]
+        listen: std::net::SocketAddr,
+    },
 
     /// Configure DPI profiles.
     ///
     /// Also allows configuring lift off distance, debouce time, and polling rate.
     ///
-    /// Note that all of these will be overriden with defaults when using this command, unless
-    /// otherwise specified.
-    // TODO: Allow selecting a specific profile instead of just the first one
-    #[command(short_flag = 'd')]
+    /// Settings which aren't specified keep their current values. Without the unverified-reads
+    /// feature, these are the settings last sent by i2control, cached in
+    /// $XDG_STATE_HOME/i2control/dpi.toml, and they're reset to defaults if nothing has been cached.
+    /// With the feature, they're read back from the mouse, and nothing is sent if they can't be
+    /// read.
+    #[command(short_flag = 'd', args_conflicts_with_subcommands = true)]
     Dpi {
+        /// Index of the DPI stage to make active (0 to 5 inclusive).
+        ///
+        /// If the stages are changed and the active stage no longer exists, the first stage is
+        /// made active.
+        #[arg(short = 'a', long = "active")]
+        #[arg(value_parser = range!(u8, dpi::STAGE_INDEX_RANGE))]
+        active_stage: Option<u8>,
+
         /// Lift off distance (mm).
         ///
         /// The maximum distance from a surface at which the mouse will register movement.
         ///
         /// Only values of 1 or 2 are accepted.
         #[arg(short = 'l', long = "lift")]
-        #[arg(default_value_t = 0x01, value_parser = range!(u8, 0x01, 0x2))]
-        lift_off_distance: u8,
+        #[arg(value_parser = range!(u8, dpi::LIFT_OFF_DISTANCE_RANGE))]
+        lift_off_distance: Option<u8>,
 
         /// Debounce time (ms).
         ///
         /// Must be between 0 and 16 (inclusive).
         /// Odd values will be rounded up.
         #[arg(short = 'd', long = "debounce")]
-        #[arg(default_value_t = 0x02, value_parser = range!(u8, 0x00, 0x10))]
-        debounce_time: u8,
+        #[arg(value_parser = range!(u8, dpi::DEBOUNCE_TIME_RANGE))]
+        debounce_time: Option<u8>,
 
         /// Polling rate (Hz).
         ///
         /// Maximum value of 1000Hz, and will be rounded to the nearest of 125Hz, 250Hz, 500Hz, or
         /// 1000Hz.
         #[arg(short = 'p', long = "polling")]
-        #[arg(default_value_t = 1000, value_parser = range!(u16, 0, 1000))]
-        polling_rate: u16,
+        #[arg(value_parser = range!(u16, dpi::POLLING_RATE_RANGE))]
+        polling_rate: Option<u16>,
 
         /// DPI Stages.
         ///
         /// Each stage has a maximum value of 26000, and will be rounded to the nearest multiple of
-        /// 50.
-        #[arg(required = true, num_args = 1..=6, value_parser = range!(u16, 50, 26000))]
+        /// 50. Stages which already exist keep their colour, and new stages are #FFA40D unless
+        /// given a colour.
+        #[arg(num_args = 0..=6, value_parser = range!(u16, dpi::DPI_RANGE))]
         dpi_stages: Vec<u16>,
+
+        /// Indicator colours of the DPI stages, in order, separated by commas.
+        ///
+        /// Only #FFA40D and #26B4FF are accepted, the stage colours seen in captures of Glorious
+        /// Core. Stages without a colour keep their current colour.
+        #[arg(short = 'c', long = "colours", alias = "colors")]
+        #[arg(num_args = 1..=6, value_delimiter = ',')]
+        colours: Vec<Rgb>,
+
+        /// Replace unsupported colours with the nearest accepted colour instead of rejecting them.
+        #[arg(long = "snap")]
+        snap: bool,
+
+        #[command(subcommand)]
+        command: Option<DpiCommand>,
     },
 
     /// Set the global inactivity timeout.
@@ -110,7 +327,150 @@ pub enum Commands {
         ///
         /// A value of 100 is rendered as infinity in Glorious Core.
         /// It is untested whether this is equivalent to disabling the timeout.
-        #[arg(value_parser = range!(u8, 0x00, 0x64))]
+        #[arg(value_parser = range!(u8, timeout::MINUTES_RANGE))]
         minutes: Option<u8>,
     },
 }
+
+/// DPI subcommands
+#[derive(Debug, Subcommand)]
+pub enum DpiCommand {
+    /// Configure a single DPI stage, keeping the others.
+    Stage {
+        /// Index of the DPI stage (0 to 5 inclusive).
+        #[arg(value_parser = range!(u8, dpi::STAGE_INDEX_RANGE))]
+        index: u8,
+
+        /// New DPI of the stage.
+        ///
+        /// Has a maximum value of 26000, and will be rounded to the nearest multiple of 50.
+        #[arg(short = 's', long = "set")]
+        #[arg(required_unless_present_any = ["activate", "colour"])]
+        #[arg(value_parser = range!(u16, dpi::DPI_RANGE))]
+        dpi: Option<u16>,
+
+        /// New indicator colour of the stage.
+        ///
+        /// Only #FFA40D and #26B4FF are accepted, the stage colours seen in captures of Glorious
+        /// Core.
+        #[arg(short = 'c', long = "colour", alias = "color")]
+        colour: Option<Rgb>,
+
+        /// Replace an unsupported colour with the nearest accepted colour instead of rejecting it.
+        #[arg(long = "snap")]
+        snap: bool,
+
+        /// Make the stage active.
+        #[arg(short = 'a', long = "activate")]
+        activate: bool,
+    },
+}
+
+/// Options for monitoring the battery
+#[derive(Debug, clap::Args)]
+pub struct BatteryWatch {
+    /// Keep reading the battery, printing each change and warning when it runs low.
+    #[arg(short = 'w', long = "watch")]
+    pub enabled: bool,
+
+    /// Time between readings (s).
+    #[arg(long = "interval", default_value_t = 60, requires = "enabled")]
+    pub interval: u64,
+
+    /// Percentages to warn below, separated by commas.
+    #[arg(long = "thresholds", value_delimiter = ',', default_values_t = [20, 5])]
+    #[arg(requires = "enabled", value_parser = range!(u8, 0..=100))]
+    pub thresholds: Vec<u8>,
+
+    /// Command to run for each warning or change in charging state, using `sh -c`.
+    ///
+    /// The event ("low", "charging" or "discharging"), percentage and charging state are passed
+    /// in the I2CONTROL_EVENT, I2CONTROL_BATTERY and I2CONTROL_CHARGING environment variables.
+    #[arg(long = "hook", requires = "enabled")]
+    pub hook: Option<String>,
+
+    /// Send a desktop notification for each warning or change in charging state, using
+    /// notify-send.
+    #[arg(long = "notify", requires = "enabled")]
+    pub notify: bool,
+}
+
+/// Settings which can be read back from the mouse
+#[cfg(feature = "unverified-reads")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Setting {
+    All,
+    #[value(name = "rgb")]
+    Lighting,
+    Dpi,
+}
+
+/// Formats for printing reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+    /// One report per line, as space-separated hex bytes
+    Hex,
+    /// Hex dumps as exported by Wireshark, separated by blank lines
+    Capture,
+}
+
+impl Cli {
+    /// Returns the maximum level of log messages to print.
+    pub fn log_level(&self) -> LevelFilter {
+        logging::level(self.quiet, self.verbose)
+    }
+}
+
+/// Lighting effects which can be animated
+#[derive(Debug, Clone, Subcommand)]
+pub enum Effect {
+    /// Fade from one colour to another and back
+    Gradient {
+        from: Rgb,
+        to: Rgb,
+
+        /// Seconds to fade there and back.
+        #[arg(long = "period", default_value = "4", value_parser = seconds)]
+        period: Duration,
+    },
+
+    /// Fade through each colour of a palette in turn
+    Cycle {
+        /// Colours, separated by commas.
+        #[arg(required = true, value_delimiter = ',')]
+        palette: Vec<Rgb>,
+
+        /// Seconds to fade from one colour to the next.
+        #[arg(long = "step", default_value = "2", value_parser = seconds)]
+        step: Duration,
+    },
+
+    /// Show a colour, flashing another colour for each line read from stdin
+    ///
+    /// E.g. `dbus-monitor "interface='org.freedesktop.Notifications',member='Notify'" |
+    /// i2control animate pulse black white` flashes on each notification.
+    Pulse {
+        idle: Rgb,
+        colour: Rgb,
+
+        /// Seconds to fade back to the idle colour.
+        #[arg(long = "fade", default_value = "1.5", value_parser = seconds)]
+        fade: Duration,
+    },
+
+    /// Play a keyframe file, with a time in seconds and a colour on each line
+    Play {
+        path: std::path::PathBuf,
+
+        /// Start again after the last keyframe.
+        #[arg(long = "loop")]
+        looping: bool,
+    },
+}
+
+/// Output formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
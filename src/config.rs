@@ -0,0 +1,306 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "unverified-reads")]
+use crate::device::{dpi::get_dpi, lighting::get_lighting};
+use crate::{
+    cache::DpiCache,
+    device::{
+        dpi::{DpiSettings, DpiUpdate, StageColour, resolve_dpi, set_dpi_settings},
+        lighting::{LightingSettings, set_lighting_settings},
+        timeout::{Timeout, set_timeout},
+        transport::Transport,
+    },
+    error::ValidationError,
+    rules::RulesConfig,
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+/// A mouse setup, as described by a TOML file.
+///
+/// Every section is optional, and settings which aren't given are left unchanged on the mouse.
+/// Example:
+/// ```
+/// # use i2control::config::Config;
+/// let config: Config = r##"
+///     timeout = 10 # or "disabled"
+///
+///     [lighting]
+///     mode = "breathing"
+///     colour = "#FF0000"
+///     brightness = 20
+///     rate = 12
+///     wireless_brightness = 5 # defaults to brightness
+///
+///     [dpi]
+///     active_stage = 1
+///     stages = [800, 1600]
+///     colours = ["#FFA40D", "#26B4FF"]
+///     lift_off_distance = 1
+///     debounce_time = 2
+///     polling_rate = 1000
+/// "##.parse().unwrap();
+/// config.validate().unwrap();
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Timeout>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lighting: Option<LightingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dpi: Option<DpiConfig>,
+    /// Lighting to show depending on system state, which is only used by `i2control rules`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<RulesConfig>,
+}
+
+impl Config {
+    /// Reads a config from a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Ok(fs::read_to_string(path)?.parse()?)
+    }
+
+    /// Writes the config as TOML, in the format accepted by [`Config::load`].
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Checks every section, using the same ranges as the individual commands.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if let Some(lighting) = &self.lighting {
+            lighting.settings().validate()?;
+        }
+
+        if let Some(dpi) = &self.dpi {
+            dpi.update().validate()?;
+        }
+
+        if let Some(timeout) = &self.timeout {
+            timeout.validate()?;
+        }
+
+        if let Some(rules) = &self.rules {
+            rules.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s)
+    }
+}
+
+/// Lighting section of a [`Config`].
+///
+/// Colours default to the ones Glorious Core sends for the mode, and the wireless brightness and
+/// rate default to the wired ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightingConfig {
+    #[serde(flatten)]
+    pub mode: LightingMode,
+    /// Ranges from 0 to 20 (inclusive)
+    pub brightness: u8,
+    /// Ranges from 1 to 20 (inclusive)
+    pub rate: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wireless_brightness: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wireless_rate: Option<u8>,
+    /// Full palette, which must have as many colours as the mode requires.
+    #[serde(alias = "colors", skip_serializing_if = "Option::is_none")]
+    pub colours: Option<Vec<Rgb>>,
+}
+
+impl From<&LightingSettings> for LightingConfig {
+    fn from(settings: &LightingSettings) -> Self {
+        let differs = |wired, wireless| (wireless != wired).then_some(wireless);
+        Self {
+            mode: settings.mode,
+            brightness: settings.brightness,
+            rate: settings.rate,
+            wireless_brightness: differs(settings.brightness, settings.wireless_brightness),
+            wireless_rate: differs(settings.rate, settings.wireless_rate),
+            colours: (settings.colours != settings.mode.default_colours())
+                .then(|| settings.colours.clone()),
+        }
+    }
+}
+
+impl LightingConfig {
+    pub fn settings(&self) -> LightingSettings {
+        let settings = LightingSettings::new(self.mode, self.brightness, self.rate).with_wireless(
+            self.wireless_brightness.unwrap_or(self.brightness),
+            self.wireless_rate.unwrap_or(self.rate),
+        );
+
+        match &self.colours {
+            Some(colours) => settings.with_colours(colours.clone()),
+            None => settings,
+        }
+    }
+}
+
+/// DPI section of a [`Config`]. Settings which are missing keep their current values, see
+/// [`resolve_dpi`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DpiConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_stage: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stages: Option<Vec<u16>>,
+    /// Colours of the stages, in order.
+    ///
+    /// The mouse only accepts colours from Glorious Core's palette, see [`StageColour`]. Colours
+    /// other than the ones seen in captures are rejected, unless the stage already has that colour
+    /// (see [`resolve_dpi`]), so settings exported from a mouse set up with Glorious Core can be
+    /// applied again.
+    #[serde(alias = "colors", skip_serializing_if = "Option::is_none")]
+    pub colours: Option<Vec<Rgb>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lift_off_distance: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debounce_time: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub polling_rate: Option<u16>,
+}
+
+impl From<&DpiSettings> for DpiConfig {
+    fn from(settings: &DpiSettings) -> Self {
+        Self {
+            active_stage: Some(settings.active_stage),
+            stages: Some(settings.stages.iter().map(|s| s.dpi).collect()),
+            colours: Some(settings.stages.iter().map(|s| s.colour).collect()),
+            lift_off_distance: Some(settings.lift_off_distance),
+            debounce_time: Some(settings.debounce_time),
+            polling_rate: Some(settings.polling_rate),
+        }
+    }
+}
+
+impl DpiConfig {
+    /// Converts the section into an update of the mouse's DPI settings.
+    pub fn update(&self) -> DpiUpdate {
+        let colours = self.colours.as_ref().map(|colours| {
+            colours
+                .iter()
+                .map(|c| StageColour::try_from(*c).unwrap_or(StageColour::Other(*c)))
+                .collect()
+        });
+
+        DpiUpdate {
+            active_stage: self.active_stage,
+            stages: self.stages.clone(),
+            colours,
+            lift_off_distance: self.lift_off_distance,
+            debounce_time: self.debounce_time,
+            polling_rate: self.polling_rate,
+            ..Default::default()
+        }
+    }
+}
+
+/// Sends every section of the config to the mouse, keeping DPI settings which aren't given as
+/// described in [`resolve_dpi`].
+///
+/// The whole config is validated and the DPI settings are resolved first, so nothing is sent if any
+/// section is invalid.
+pub fn apply_config(
+    mouse: &(impl Transport + ?Sized),
+    config: &Config,
+    cache: Option<&DpiCache>,
+) -> crate::Result<()> {
+    config.validate()?;
+
+    let dpi = config
+        .dpi
+        .as_ref()
+        .map(|dpi| resolve_dpi(mouse, &dpi.update(), cache))
+        .transpose()?;
+
+    if let Some(lighting) = &config.lighting {
+        set_lighting_settings(mouse, &lighting.settings())?;
+    }
+
+    if let Some(dpi) = &dpi {
+        set_dpi_settings(mouse, dpi, cache)?;
+    }
+
+    if let Some(timeout) = config.timeout {
+        set_timeout(mouse, timeout)?;
+    }
+
+    Ok(())
+}
+
+/// Reads the lighting and DPI settings from the mouse, so they can be applied again with
+/// [`apply_config`].
+///
+/// The requests are guesses, see [`read_header`](crate::device::message::read_header). The timeout
+/// is left out, since the guessed request would change it.
+#[cfg(feature = "unverified-reads")]
+pub fn get_config(mouse: &(impl Transport + ?Sized)) -> crate::Result<Config> {
+    Ok(Config {
+        timeout: None,
+        lighting: Some(LightingConfig::from(&get_lighting(mouse)?)),
+        dpi: Some(DpiConfig::from(&get_dpi(mouse)?)),
+        rules: None, // Not stored on the mouse
+    })
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    /// A section required by a command is missing.
+    MissingSection(&'static str),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "Couldn't read config: {e}"),
+            ConfigError::Parse(e) => write!(f, "Invalid config: {e}"),
+            ConfigError::Serialize(e) => write!(f, "Couldn't write config: {e}"),
+            ConfigError::MissingSection(name) => write!(f, "Config has no [{name}] section"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::Serialize(e) => Some(e),
+            ConfigError::MissingSection(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        Self::Serialize(e)
+    }
+}
@@ -0,0 +1,185 @@
+use serde::Serialize;
+
+#[cfg(feature = "unverified-reads")]
+use super::{
+    message::{
+        DEFAULT_HEADER_LEN, DecodeError, DecodeResult, MessageBuilder, Report, default_header,
+    },
+    transport::Transport,
+};
+
+/// The battery query hasn't been captured from Glorious Core yet, so it's only built with the
+/// `unverified-reads` feature. The operation ID and reply layout are guesses following the other
+/// operations: a single report with a default header, which the mouse answers with a report
+/// carrying the same header.
+#[cfg(feature = "unverified-reads")]
+const OPERATION_ID: u8 = 0x08;
+
+/// Battery level and charging state of a mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BatteryStatus {
+    /// Ranges from 0 to 100 (inclusive)
+    pub percentage: u8,
+    pub charging: bool,
+}
+
+impl BatteryStatus {
+    /// Reads the battery status from the mouse's reply.
+    ///
+    /// Byte 6 of the reply holds the percentage, and byte 7 is 0x01 while charging.
+    #[cfg(feature = "unverified-reads")]
+    fn from_report(report: &Report) -> DecodeResult<Self> {
+        let data = report.data();
+        let header = &data[..DEFAULT_HEADER_LEN];
+        if header != default_header(OPERATION_ID, 0x00) {
+            return Err(DecodeError::HeaderError {
+                report_i: 0,
+                header: header.to_vec(),
+            });
+        }
+
+        let percentage = data[DEFAULT_HEADER_LEN];
+        if percentage > 100 {
+            return Err(DecodeError::ValueError {
+                field: "battery percentage",
+                value: percentage.into(),
+            });
+        }
+
+        let charging = match data[DEFAULT_HEADER_LEN + 1] {
+            0x00 => false,
+            0x01 => true,
+            value => {
+                return Err(DecodeError::ValueError {
+                    field: "charging state",
+                    value: value.into(),
+                });
+            }
+        };
+
+        Ok(Self {
+            percentage,
+            charging,
+        })
+    }
+
+    /// Returns either "charging" or "discharging".
+    pub fn charging_state(&self) -> &'static str {
+        if self.charging {
+            "charging"
+        } else {
+            "discharging"
+        }
+    }
+}
+
+impl std::fmt::Display for BatteryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}% ({})", self.percentage, self.charging_state())
+    }
+}
+
+/// A change in battery state worth telling the user about.
+///
+/// Serialised with the name of the event under `event`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "event")]
+pub enum BatteryEvent {
+    /// The percentage dropped below the threshold while discharging.
+    #[serde(rename = "low")]
+    Low { threshold: u8 },
+    #[serde(rename = "charging")]
+    ChargingStarted,
+    #[serde(rename = "discharging")]
+    ChargingStopped,
+}
+
+impl BatteryEvent {
+    /// Short name of the event, for hook commands.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatteryEvent::Low { threshold: _ } => "low",
+            BatteryEvent::ChargingStarted => "charging",
+            BatteryEvent::ChargingStopped => "discharging",
+        }
+    }
+}
+
+impl std::fmt::Display for BatteryEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatteryEvent::Low { threshold } => write!(f, "Battery below {threshold}%"),
+            BatteryEvent::ChargingStarted => write!(f, "Charging started"),
+            BatteryEvent::ChargingStopped => write!(f, "Charging stopped"),
+        }
+    }
+}
+
+/// Turns successive battery readings into [`BatteryEvent`]s.
+#[derive(Debug, Clone)]
+pub struct BatteryMonitor {
+    thresholds: Vec<u8>,
+    last: Option<BatteryStatus>,
+}
+
+impl BatteryMonitor {
+    /// Creates a monitor which warns when the percentage drops below any of the thresholds.
+    pub fn new(mut thresholds: Vec<u8>) -> Self {
+        thresholds.sort_unstable();
+        Self {
+            thresholds,
+            last: None,
+        }
+    }
+
+    /// Returns the previous reading, if any.
+    pub fn last(&self) -> Option<BatteryStatus> {
+        self.last
+    }
+
+    /// Records a new reading, returning the events since the previous one.
+    ///
+    /// If several thresholds were crossed at once, only the lowest is reported. A reading which is
+    /// already below a threshold when monitoring starts counts as crossing it.
+    pub fn update(&mut self, status: BatteryStatus) -> Vec<BatteryEvent> {
+        let mut events = Vec::new();
+        let previous = self.last.replace(status);
+
+        match previous {
+            Some(previous) if !previous.charging && status.charging => {
+                events.push(BatteryEvent::ChargingStarted)
+            }
+            Some(previous) if previous.charging && !status.charging => {
+                events.push(BatteryEvent::ChargingStopped)
+            }
+            _ => (),
+        }
+
+        if !status.charging {
+            let was_above = |t: u8| match previous {
+                Some(previous) => previous.charging || previous.percentage >= t,
+                None => true,
+            };
+
+            let crossed = self
+                .thresholds
+                .iter()
+                .copied()
+                .find(|&t| status.percentage < t && was_above(t));
+            if let Some(threshold) = crossed {
+                events.push(BatteryEvent::Low { threshold });
+            }
+        }
+
+        events
+    }
+}
+
+/// Reads the battery status from the mouse.
+///
+/// The request and reply are guesses, which haven't been checked against a capture.
+#[cfg(feature = "unverified-reads")]
+pub fn get_battery(mouse: &(impl Transport + ?Sized)) -> crate::Result<BatteryStatus> {
+    let replies = MessageBuilder::new(OPERATION_ID, 1).build()?.query(mouse)?;
+    Ok(BatteryStatus::from_report(&replies[0])?)
+}
@@ -0,0 +1,92 @@
+use super::{
+    dpi::{self, DpiSettings},
+    lighting::{self, LightingSettings},
+    message::{DecodeError, DecodeResult, Report},
+    timeout::{self, Timeout},
+};
+
+/// Settings carried by a decoded message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Lighting(LightingSettings),
+    Dpi(DpiSettings),
+    Timeout(Timeout),
+}
+
+impl Operation {
+    /// Returns the number of reports in messages with the given operation ID, if it's known.
+    pub fn num_reports(operation: u8) -> Option<u8> {
+        match operation {
+            lighting::OPERATION_ID => Some(lighting::NUM_REPORTS),
+            dpi::OPERATION_ID => Some(dpi::NUM_REPORTS),
+            timeout::OPERATION_ID => Some(timeout::NUM_REPORTS),
+            _ => None,
+        }
+    }
+
+    /// Decodes all reports of a single message, choosing the operation from the first header.
+    pub fn decode(reports: &[Report]) -> DecodeResult<Self> {
+        let operation = reports
+            .first()
+            .ok_or(DecodeError::DataLenError { report_i: 0 })?
+            .operation();
+
+        match operation {
+            lighting::OPERATION_ID => Ok(Operation::Lighting(LightingSettings::from_reports(
+                reports,
+            )?)),
+            dpi::OPERATION_ID => Ok(Operation::Dpi(DpiSettings::from_reports(reports)?)),
+            timeout::OPERATION_ID => Ok(Operation::Timeout(Timeout::from_reports(reports)?)),
+            operation => Err(DecodeError::OperationError { operation }),
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Lighting(settings) => settings.fmt(f),
+            Operation::Dpi(settings) => settings.fmt(f),
+            Operation::Timeout(timeout) => timeout.fmt(f),
+        }
+    }
+}
+
+/// Splits a sequence of reports into messages and decodes each one.
+///
+/// Each message is taken to be the number of reports its operation uses, or fewer if a report for
+/// another operation comes first. Reports for unknown operations are decoded one at a time.
+pub struct Decoder<'a> {
+    reports: &'a [Report],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(reports: &'a [Report]) -> Self {
+        Self { reports }
+    }
+
+    /// Returns the number of reports in the message at the start of the remaining reports.
+    fn message_len(&self) -> usize {
+        let operation = self.reports[0].operation();
+        let num_reports = Operation::num_reports(operation).unwrap_or(1) as usize;
+        self.reports
+            .iter()
+            .take(num_reports)
+            .take_while(|r| r.operation() == operation)
+            .count()
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = DecodeResult<Operation>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reports.is_empty() {
+            return None;
+        }
+
+        let (message, rest) = self.reports.split_at(self.message_len());
+        self.reports = rest;
+        Some(Operation::decode(message))
+    }
+}
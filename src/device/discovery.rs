@@ -0,0 +1,258 @@
+use std::ffi::{CStr, CString};
+
+use hidapi::{DeviceInfo, HidApi, HidDevice, HidResult};
+use serde::{Serialize, ser::SerializeStruct};
+
+use crate::Error;
+
+use super::message::{REPORT_ID, REPORT_LEN};
+
+/// Glorious Vendor ID
+pub const GLORIOUS_VID: u16 = 0x93A;
+
+/// Model I2 Wireless 2.4GHz wireless mode Product ID
+pub const I2_WL_PID: u16 = 0x821D;
+
+/// Model I2 Wireless wired mode Product ID
+pub const I2_WIRED_PID: u16 = 0x821A;
+
+/// How a mouse is connected to the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionMode {
+    /// Connected directly over the USB cable.
+    Wired,
+    /// Connected through the 2.4GHz USB dongle.
+    Wireless,
+}
+
+impl std::fmt::Display for ConnectionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionMode::Wired => write!(f, "wired"),
+            ConnectionMode::Wireless => write!(f, "2.4GHz wireless"),
+        }
+    }
+}
+
+/// A VID/PID pair known to belong to a supported mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct KnownDevice {
+    pub name: &'static str,
+    pub vid: u16,
+    pub pid: u16,
+    pub mode: ConnectionMode,
+}
+
+/// All supported models and connection modes.
+///
+/// Both modes of a mouse use the same protocol, so every operation works regardless of how the
+/// mouse is connected.
+pub const KNOWN_DEVICES: &[KnownDevice] = &[
+    KnownDevice {
+        name: "Model I2 Wireless",
+        vid: GLORIOUS_VID,
+        pid: I2_WIRED_PID,
+        mode: ConnectionMode::Wired,
+    },
+    KnownDevice {
+        name: "Model I2 Wireless",
+        vid: GLORIOUS_VID,
+        pid: I2_WL_PID,
+        mode: ConnectionMode::Wireless,
+    },
+];
+
+/// Returns the known device entry matching the given IDs, if any.
+pub fn known_device(vid: u16, pid: u16) -> Option<&'static KnownDevice> {
+    KNOWN_DEVICES.iter().find(|d| d.vid == vid && d.pid == pid)
+}
+
+/// Returns the connection mode of an opened device, if it is a known device.
+pub fn connection_mode(mouse: &HidDevice) -> HidResult<Option<ConnectionMode>> {
+    let info = mouse.get_device_info()?;
+    Ok(known_device(info.vendor_id(), info.product_id()).map(|d| d.mode))
+}
+
+/// A connected HID interface belonging to a supported mouse.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    path: CString,
+    model: &'static KnownDevice,
+}
+
+impl Candidate {
+    /// Creates a candidate for the interface at the given hidapi path.
+    pub fn new(path: CString, model: &'static KnownDevice) -> Self {
+        Self { path, model }
+    }
+
+    /// Returns the hidapi path of the interface.
+    pub fn path(&self) -> &CStr {
+        &self.path
+    }
+
+    pub fn model(&self) -> &'static KnownDevice {
+        self.model
+    }
+
+    pub fn mode(&self) -> ConnectionMode {
+        self.model.mode
+    }
+
+    pub fn open(&self, api: &HidApi) -> crate::Result<HidDevice> {
+        api.open_path(&self.path)
+            .map_err(|e| Error::from_open(e, &self.path.to_string_lossy()))
+    }
+}
+
+impl std::fmt::Display for Candidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) [{:04X}:{:04X}] at {}",
+            self.model.name,
+            self.model.mode,
+            self.model.vid,
+            self.model.pid,
+            self.path.to_string_lossy()
+        )
+    }
+}
+
+/// Candidates are serialised as their model, along with the path of the interface.
+impl Serialize for Candidate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Candidate", 5)?;
+        s.serialize_field("name", self.model.name)?;
+        s.serialize_field("vid", &self.model.vid)?;
+        s.serialize_field("pid", &self.model.pid)?;
+        s.serialize_field("mode", &self.model.mode)?;
+        s.serialize_field("path", &self.path.to_string_lossy())?;
+        s.end()
+    }
+}
+
+/// Checks whether the given interface accepts feature reports with [`REPORT_ID`].
+///
+/// Each mouse exposes several HID interfaces, but only one of them handles configuration
+/// messages. The others reject the report, or cannot be opened at all.
+fn accepts_reports(api: &HidApi, info: &DeviceInfo) -> HidResult<bool> {
+    let device = info.open_device(api)?;
+
+    let mut buf = [0x00; REPORT_LEN];
+    buf[0] = REPORT_ID;
+    Ok(device.get_feature_report(&mut buf).is_ok())
+}
+
+/// Returns every HID interface matching one of [`KNOWN_DEVICES`], whether or not it accepts
+/// configuration messages.
+pub fn matching_interfaces(api: &HidApi) -> impl Iterator<Item = &DeviceInfo> {
+    api.device_list()
+        .filter(|info| known_device(info.vendor_id(), info.product_id()).is_some())
+}
+
+/// Finds all connected supported mice.
+///
+/// Only the configuration interface of each mouse is returned, so each candidate corresponds to
+/// a single physical device.
+///
+/// Fails with [`Error::PermissionDenied`] if no devices were found because their interfaces
+/// couldn't be opened.
+pub fn find_devices(api: &HidApi) -> crate::Result<Vec<Candidate>> {
+    let mut candidates = Vec::new();
+    let mut denied = None;
+
+    for info in matching_interfaces(api) {
+        match accepts_reports(api, info) {
+            Ok(true) => candidates.push(Candidate::new(
+                info.path().to_owned(),
+                known_device(info.vendor_id(), info.product_id()).unwrap(),
+            )),
+            Ok(false) => (),
+            Err(e) => {
+                if let e @ Error::PermissionDenied { .. } =
+                    Error::from_open(e, &info.path().to_string_lossy())
+                {
+                    denied = Some(e);
+                }
+            }
+        }
+    }
+
+    match denied {
+        Some(e) if candidates.is_empty() => Err(e),
+        _ => Ok(candidates),
+    }
+}
+
+/// Checks whether the candidates are most likely the same mouse, connected both over the cable and
+/// through its dongle.
+///
+/// The dongle stays enumerated while the mouse is charging, but the mouse switches to the cable, so
+/// the wired connection should be used.
+fn wired_and_dongle(candidates: &[Candidate]) -> Option<usize> {
+    let [a, b] = candidates else {
+        return None;
+    };
+
+    if a.model.name != b.model.name || a.mode() == b.mode() {
+        return None;
+    }
+
+    candidates
+        .iter()
+        .position(|c| c.mode() == ConnectionMode::Wired)
+}
+
+/// Picks a single device from the given candidates.
+///
+/// If `index` is `None`, there must be exactly one candidate, or a single mouse connected in both
+/// wired and wireless mode.
+pub fn select_device(
+    candidates: Vec<Candidate>,
+    index: Option<usize>,
+) -> DiscoveryResult<Candidate> {
+    match (index, candidates.len()) {
+        (Some(i), len) if i >= len => Err(DiscoveryError::InvalidIndex { index: i, len }),
+        (Some(i), _) => Ok(candidates.into_iter().nth(i).unwrap()),
+        (None, 0) => Err(DiscoveryError::NoDevices),
+        (None, 1) => Ok(candidates.into_iter().next().unwrap()),
+        (None, _) => match wired_and_dongle(&candidates) {
+            Some(i) => Ok(candidates.into_iter().nth(i).unwrap()),
+            None => Err(DiscoveryError::MultipleDevices(candidates)),
+        },
+    }
+}
+
+type DiscoveryResult<T> = Result<T, DiscoveryError>;
+
+#[derive(Debug)]
+pub enum DiscoveryError {
+    NoDevices,
+    MultipleDevices(Vec<Candidate>),
+    InvalidIndex { index: usize, len: usize },
+}
+
+impl std::fmt::Display for DiscoveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiscoveryError::NoDevices => write!(f, "No supported devices found"),
+            DiscoveryError::MultipleDevices(candidates) => {
+                write!(
+                    f,
+                    "Multiple supported devices found, select one with --device:"
+                )?;
+                for (i, candidate) in candidates.iter().enumerate() {
+                    write!(f, "\n  {i}: {candidate}")?;
+                }
+                Ok(())
+            }
+            DiscoveryError::InvalidIndex { index, len } => {
+                write!(f, "Device {index} does not exist ({len} found)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiscoveryError {}
@@ -1,18 +1,100 @@
-use std::error::Error;
+use std::ops::RangeInclusive;
 
-use hidapi::HidDevice;
+#[cfg(not(feature = "unverified-reads"))]
+use log::warn;
+use serde::{Deserialize, Serialize};
 
-use crate::{rgb, util::rgb::Rgb};
+use crate::{
+    cache::DpiCache,
+    error::{ValidationError, check_range},
+    rgb,
+    util::rgb::Rgb,
+};
 
-use super::message::MessageBuilder;
+use super::{
+    message::{
+        DecodeError, DecodeResult, Message, MessageBuilder, MessageBuilderResult, MessageReader,
+        Report,
+    },
+    transport::Transport,
+};
 
-const OPERATION_ID: u8 = 0x04;
+pub const OPERATION_ID: u8 = 0x04;
+
+pub const NUM_REPORTS: u8 = 4;
 
 const POLLING_RATES: [u16; 4] = [125, 250, 500, 1000];
 
+/// Maximum number of DPI stages.
+pub const MAX_STAGES: usize = 6;
+
+/// Indices of DPI stages.
+pub const STAGE_INDEX_RANGE: RangeInclusive<u8> = 0x00..=0x05;
+
+/// DPI of each stage. Rounded to the nearest multiple of 50 when sent.
+pub const DPI_RANGE: RangeInclusive<u16> = 50..=26000;
+
+/// Lift off distance (mm).
+pub const LIFT_OFF_DISTANCE_RANGE: RangeInclusive<u8> = 0x01..=0x02;
+
+/// Debounce time (ms). Odd values are rounded up when sent.
+pub const DEBOUNCE_TIME_RANGE: RangeInclusive<u8> = 0x00..=0x10;
+
+/// Polling rate (Hz). Rounded to the nearest supported polling rate when sent.
+pub const POLLING_RATE_RANGE: RangeInclusive<u16> = 0..=1000;
+
 /// For some reason, setting a DPI stage also requires a valid colour to be sent to the mouse (i.e.
-/// one of the colours from Glorious Core). This is the first one of those.
-const STAGE_RGB: [u8; 3] = rgb!(0xFFA40D).bytes();
+/// one of the colours from Glorious Core). Other colours aren't accepted.
+///
+/// Only the colours seen in captures of Glorious Core are included here, which isn't its whole
+/// stage palette. The other colours it offers haven't been captured yet, so they're rejected unless
+/// they were read back from a mouse.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StageColour {
+    #[default]
+    Orange,
+    Blue,
+    /// A colour read back from a mouse, e.g. one set by Glorious Core, which isn't one of the
+    /// captured colours. It's only sent for a stage which already has it, see [`resolve_dpi`].
+    Other(Rgb),
+}
+
+impl StageColour {
+    pub const ALL: [StageColour; 2] = [StageColour::Orange, StageColour::Blue];
+
+    pub const fn rgb(&self) -> Rgb {
+        match self {
+            StageColour::Orange => rgb!(0xFFA40D),
+            StageColour::Blue => rgb!(0x26B4FF),
+            StageColour::Other(col) => *col,
+        }
+    }
+
+    /// Returns the stage colour closest to the given colour.
+    pub fn nearest(col: Rgb) -> Self {
+        let dist = |c: &StageColour| {
+            c.rgb()
+                .bytes()
+                .into_iter()
+                .zip(col.bytes())
+                .map(|(a, b)| (a.abs_diff(b) as u32).pow(2))
+                .sum::<u32>()
+        };
+
+        Self::ALL.into_iter().min_by_key(dist).unwrap()
+    }
+}
+
+impl TryFrom<Rgb> for StageColour {
+    type Error = StageColourError;
+
+    fn try_from(col: Rgb) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.rgb() == col)
+            .ok_or(StageColourError(col))
+    }
+}
 
 fn polling_rate_id(polling_rate: u16) -> u8 {
     let (i, _) = POLLING_RATES
@@ -25,38 +107,546 @@ fn polling_rate_id(polling_rate: u16) -> u8 {
     i as u8 + 1
 }
 
+/// A single DPI stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DpiStage {
+    pub dpi: u16,
+    /// Colour of the stage in Glorious Core.
+    pub colour: Rgb,
+}
+
+impl DpiStage {
+    pub fn new(dpi: u16) -> Self {
+        Self {
+            dpi,
+            colour: StageColour::default().rgb(),
+        }
+    }
+}
+
+/// DPI stages, along with the other settings sent in the same message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DpiSettings {
+    /// Index of the active DPI stage.
+    pub active_stage: u8,
+    pub stages: Vec<DpiStage>,
+    /// Lift off distance (mm).
+    pub lift_off_distance: u8,
+    /// Debounce time (ms).
+    pub debounce_time: u8,
+    /// Polling rate (Hz).
+    pub polling_rate: u16,
+}
+
+impl DpiSettings {
+    /// Makes the stage with the given index active.
+    pub fn select_stage(&mut self, index: u8) -> Result<(), StageIndexError> {
+        self.check_stage(index)?;
+        self.active_stage = index;
+        Ok(())
+    }
+
+    /// Changes the DPI of the stage with the given index.
+    pub fn set_stage_dpi(&mut self, index: u8, dpi: u16) -> Result<(), StageIndexError> {
+        self.check_stage(index)?;
+        self.stages[index as usize].dpi = dpi;
+        Ok(())
+    }
+
+    /// Changes the colour of the stage with the given index.
+    pub fn set_stage_colour(
+        &mut self,
+        index: u8,
+        colour: StageColour,
+    ) -> Result<(), StageIndexError> {
+        self.check_stage(index)?;
+        self.stages[index as usize].colour = colour.rgb();
+        Ok(())
+    }
+
+    fn check_stage(&self, index: u8) -> Result<(), StageIndexError> {
+        if (index as usize) < self.stages.len() {
+            Ok(())
+        } else {
+            Err(StageIndexError {
+                index,
+                num_stages: self.stages.len(),
+            })
+        }
+    }
+
+    /// Checks that all settings are within their allowed ranges.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        check_range("number of DPI stages", self.stages.len(), 0..=MAX_STAGES)?;
+        if !self.stages.is_empty() {
+            self.check_stage(self.active_stage)?;
+        }
+
+        for stage in &self.stages {
+            check_range("DPI", stage.dpi, DPI_RANGE)?;
+        }
+
+        check_range(
+            "lift off distance",
+            self.lift_off_distance,
+            LIFT_OFF_DISTANCE_RANGE,
+        )?;
+        check_range("debounce time", self.debounce_time, DEBOUNCE_TIME_RANGE)?;
+        check_range("polling rate", self.polling_rate, POLLING_RATE_RANGE)?;
+        Ok(())
+    }
+
+    /// Builds the message setting these settings.
+    pub fn message(&self) -> MessageBuilderResult<Message> {
+        let debounce_time = self.debounce_time.div_ceil(2) * 2; // Round up to even integers
+        let polling_rate = polling_rate_id(self.polling_rate);
+
+        let mut mb = MessageBuilder::new(OPERATION_ID, NUM_REPORTS)
+            .push(self.active_stage)
+            .push(self.stages.len() as u8)
+            .push(self.lift_off_distance)
+            .push(debounce_time)
+            .push(polling_rate)
+            .push(0x00);
+
+        for stage in &self.stages {
+            let dpi = (stage.dpi + 25) / 50; // DPI must be a multiple of 50, so we divide and round
+            let [lo, hi] = dpi.to_le_bytes();
+            let [r, g, b] = stage.colour.bytes();
+            mb = mb.push_block(&[lo, hi, r, g, b]);
+        }
+
+        mb.build()
+    }
+
+    /// Decodes DPI settings from the reports of a DPI message, e.g. from a capture.
+    pub fn from_reports(reports: &[Report]) -> DecodeResult<Self> {
+        let mut reader = MessageReader::new(reports, OPERATION_ID)?;
+
+        let active_stage = reader.read()?;
+        let num_stages = reader.read()?;
+        let lift_off_distance = reader.read()?;
+        let debounce_time = reader.read()?;
+        let polling_rate = match reader.read()? {
+            id @ 0x01..=0x04 => POLLING_RATES[id as usize - 1],
+            value => {
+                return Err(DecodeError::ValueError {
+                    field: "polling rate",
+                    value: value.into(),
+                });
+            }
+        };
+        reader.read()?;
+
+        let mut stages = Vec::new();
+        for _ in 0..num_stages {
+            let block = reader.read_block(5)?;
+            let dpi = match u16::from_le_bytes([block[0], block[1]]) {
+                value @ 0x01..=0x208 => value * 50, // DPI_RANGE, in multiples of 50
+                value => {
+                    return Err(DecodeError::ValueError {
+                        field: "DPI",
+                        value,
+                    });
+                }
+            };
+
+            stages.push(DpiStage {
+                dpi,
+                colour: Rgb::new(block[2], block[3], block[4]),
+            });
+        }
+
+        Ok(Self {
+            active_stage,
+            stages,
+            lift_off_distance,
+            debounce_time,
+            polling_rate,
+        })
+    }
+}
+
+impl Default for DpiSettings {
+    /// No DPI stages, along with the defaults from Glorious Core.
+    ///
+    /// Sending no stages keeps whichever DPI the mouse previously had active.
+    fn default() -> Self {
+        Self {
+            active_stage: 0x00,
+            stages: Vec::new(),
+            lift_off_distance: 0x01,
+            debounce_time: 0x02,
+            polling_rate: 1000,
+        }
+    }
+}
+
+impl std::fmt::Display for DpiSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "DPI stages:")?;
+        for (i, stage) in self.stages.iter().enumerate() {
+            let active = if i == self.active_stage as usize {
+                " (active)"
+            } else {
+                ""
+            };
+            writeln!(f, "  {i}: {} DPI {}{active}", stage.dpi, stage.colour)?;
+        }
+
+        writeln!(f, "Lift off distance: {}mm", self.lift_off_distance)?;
+        writeln!(f, "Debounce time: {}ms", self.debounce_time)?;
+        write!(f, "Polling rate: {}Hz", self.polling_rate)
+    }
+}
+
+/// Changes to DPI settings. Fields which are `None` keep their current values.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DpiUpdate {
+    pub active_stage: Option<u8>,
+    pub stages: Option<Vec<u16>>,
+    /// Colours of the stages, in order. Stages without a colour keep their current colour.
+    pub colours: Option<Vec<StageColour>>,
+    /// Changes the DPI of a single stage, given by its index.
+    pub stage_dpi: Option<(u8, u16)>,
+    /// Changes the colour of a single stage, given by its index.
+    pub stage_colour: Option<(u8, StageColour)>,
+    pub lift_off_distance: Option<u8>,
+    pub debounce_time: Option<u8>,
+    pub polling_rate: Option<u16>,
+}
+
+impl DpiUpdate {
+    /// Returns true if every setting is changed, so the current settings aren't needed.
+    pub fn is_complete(&self) -> bool {
+        self.active_stage.is_some()
+            && self.stages.is_some()
+            && self.lift_off_distance.is_some()
+            && self.debounce_time.is_some()
+            && self.polling_rate.is_some()
+    }
+
+    /// Returns each colour which isn't one of the captured stage colours, along with the index of
+    /// its stage.
+    fn uncaptured_colours(&self) -> impl Iterator<Item = (u8, Rgb)> + '_ {
+        let colours = self.colours.iter().flatten().enumerate();
+        colours
+            .map(|(index, colour)| (index as u8, *colour))
+            .chain(self.stage_colour)
+            .filter_map(|(index, colour)| match colour {
+                StageColour::Other(col) => Some((index, col)),
+                _ => None,
+            })
+    }
+
+    /// Checks that every changed setting is within its allowed range.
+    ///
+    /// If the stages are given, stage indices and colours are checked against them. Otherwise, they
+    /// can only be checked once the update is applied.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if let Some(index) = self.active_stage {
+            check_range("active DPI stage", index, STAGE_INDEX_RANGE)?;
+        }
+
+        if let Some(stages) = &self.stages {
+            check_range("number of DPI stages", stages.len(), 0..=MAX_STAGES)?;
+            for dpi in stages {
+                check_range("DPI", *dpi, DPI_RANGE)?;
+            }
+        }
+
+        if let Some(colours) = &self.colours {
+            check_range("number of DPI stage colours", colours.len(), 0..=MAX_STAGES)?;
+        }
+
+        if let Some(stages) = &self.stages {
+            self.check_stages(stages.len())?;
+        }
+
+        if let Some((index, dpi)) = self.stage_dpi {
+            check_range("DPI stage", index, STAGE_INDEX_RANGE)?;
+            check_range("DPI", dpi, DPI_RANGE)?;
+        }
+
+        if let Some((index, _)) = self.stage_colour {
+            check_range("DPI stage", index, STAGE_INDEX_RANGE)?;
+        }
+
+        if let Some(lift_off_distance) = self.lift_off_distance {
+            check_range(
+                "lift off distance",
+                lift_off_distance,
+                LIFT_OFF_DISTANCE_RANGE,
+            )?;
+        }
+
+        if let Some(debounce_time) = self.debounce_time {
+            check_range("debounce time", debounce_time, DEBOUNCE_TIME_RANGE)?;
+        }
+
+        if let Some(polling_rate) = self.polling_rate {
+            check_range("polling rate", polling_rate, POLLING_RATE_RANGE)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every stage index and colour refers to one of the given number of stages.
+    fn check_stages(&self, num_stages: usize) -> Result<(), StageIndexError> {
+        // The first colour without a stage
+        let colour = self
+            .colours
+            .as_ref()
+            .filter(|colours| colours.len() > num_stages)
+            .map(|_| num_stages as u8);
+
+        let indices = [
+            self.active_stage,
+            colour,
+            self.stage_dpi.map(|(index, _)| index),
+            self.stage_colour.map(|(index, _)| index),
+        ];
+
+        match indices
+            .into_iter()
+            .flatten()
+            .find(|i| *i as usize >= num_stages)
+        {
+            Some(index) => Err(StageIndexError { index, num_stages }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, settings: &mut DpiSettings) -> Result<(), StageIndexError> {
+        if let Some(stages) = &self.stages {
+            // Stages which still exist keep their colour
+            settings.stages = stages
+                .iter()
+                .enumerate()
+                .map(|(i, &dpi)| match settings.stages.get(i) {
+                    Some(stage) => DpiStage { dpi, ..*stage },
+                    None => DpiStage::new(dpi),
+                })
+                .collect();
+            if settings.active_stage as usize >= settings.stages.len() {
+                settings.active_stage = 0x00;
+            }
+        }
+
+        if let Some(colours) = &self.colours {
+            for (index, colour) in colours.iter().enumerate() {
+                settings.set_stage_colour(index as u8, *colour)?;
+            }
+        }
+
+        if let Some((index, dpi)) = self.stage_dpi {
+            settings.set_stage_dpi(index, dpi)?;
+        }
+
+        if let Some((index, colour)) = self.stage_colour {
+            settings.set_stage_colour(index, colour)?;
+        }
+
+        if let Some(index) = self.active_stage {
+            settings.select_stage(index)?;
+        }
+
+        if let Some(lift_off_distance) = self.lift_off_distance {
+            settings.lift_off_distance = lift_off_distance;
+        }
+
+        if let Some(debounce_time) = self.debounce_time {
+            settings.debounce_time = debounce_time;
+        }
+
+        if let Some(polling_rate) = self.polling_rate {
+            settings.polling_rate = polling_rate;
+        }
+
+        Ok(())
+    }
+}
+
 pub fn set_dpi(
-    mouse: &HidDevice,
+    mouse: &(impl Transport + ?Sized),
     lift_off_distance: u8,
     debounce_time: u8,
     polling_rate: u16,
-    mut stages: Vec<u16>,
-) -> Result<(), Box<dyn Error>> {
-    let debounce_time = debounce_time.div_ceil(2) * 2; // Round up to even integers
-    let polling_rate = polling_rate_id(polling_rate);
-
-    for s in stages.iter_mut() {
-        *s = (*s + 25) / 50; // DPI must be a multiple of 50, so we divide and round
-    }
-
-    let mut mb = MessageBuilder::new(OPERATION_ID, 4)
-        .push(0x00) // Select first DPI stage
-        .push(stages.len() as u8)
-        .push(lift_off_distance)
-        .push(debounce_time)
-        .push(polling_rate)
-        .push(0x00);
-
-    for stage in stages {
-        mb = mb.push_block(&[
-            (stage & 0x00FF) as u8,
-            ((stage & 0xFF00) >> 8) as u8,
-            STAGE_RGB[0],
-            STAGE_RGB[1],
-            STAGE_RGB[2],
-        ]);
-    }
-
-    mb.build()?.send(mouse)?;
+    stages: Vec<u16>,
+    cache: Option<&DpiCache>,
+) -> crate::Result<()> {
+    let settings = DpiSettings {
+        active_stage: 0x00, // Select first DPI stage
+        stages: stages.into_iter().map(DpiStage::new).collect(),
+        lift_off_distance,
+        debounce_time,
+        polling_rate,
+    };
+
+    set_dpi_settings(mouse, &settings, cache)
+}
+
+/// Sends DPI settings to the mouse, and stores them in `cache` once they've been sent.
+pub fn set_dpi_settings(
+    mouse: &(impl Transport + ?Sized),
+    settings: &DpiSettings,
+    cache: Option<&DpiCache>,
+) -> crate::Result<()> {
+    settings.validate()?;
+    settings.message()?.send(mouse)?;
+    if let Some(cache) = cache {
+        cache.store(settings);
+    }
+
     Ok(())
 }
+
+/// Reads the current DPI settings from the mouse.
+///
+/// The request is a guess, see [`read_header`](super::message::read_header).
+#[cfg(feature = "unverified-reads")]
+pub fn get_dpi(mouse: &(impl Transport + ?Sized)) -> crate::Result<DpiSettings> {
+    let replies = MessageBuilder::read_request(OPERATION_ID, NUM_REPORTS)
+        .build()?
+        .query(mouse)?;
+    Ok(DpiSettings::from_reports(&replies)?)
+}
+
+/// Returns the settings an update which doesn't change every setting is applied to.
+#[cfg(feature = "unverified-reads")]
+fn current_dpi(
+    mouse: &(impl Transport + ?Sized),
+    _cache: Option<&DpiCache>,
+) -> crate::Result<DpiSettings> {
+    get_dpi(mouse)
+}
+
+/// Returns the settings an update which doesn't change every setting is applied to.
+#[cfg(not(feature = "unverified-reads"))]
+fn current_dpi(
+    _mouse: &(impl Transport + ?Sized),
+    cache: Option<&DpiCache>,
+) -> crate::Result<DpiSettings> {
+    Ok(cache.and_then(DpiCache::load).unwrap_or_else(|| {
+        warn!("The current DPI settings aren't known, so settings not given are reset to defaults");
+        DpiSettings::default()
+    }))
+}
+
+/// Returns the settings to send for an update, keeping the rest of the mouse's current settings.
+///
+/// The current settings are read back from the mouse with the `unverified-reads` feature. Without
+/// it, they're the settings last sent, as kept in `cache`. If those aren't known either, settings
+/// which the update doesn't change are reset to their defaults.
+///
+/// [`StageColour::Other`] colours are only accepted for stages which already have that colour.
+pub fn resolve_dpi(
+    mouse: &(impl Transport + ?Sized),
+    update: &DpiUpdate,
+    cache: Option<&DpiCache>,
+) -> crate::Result<DpiSettings> {
+    update.validate()?;
+
+    let mut uncaptured = update.uncaptured_colours().peekable();
+    let mut settings = if update.is_complete() && uncaptured.peek().is_none() {
+        DpiSettings::default()
+    } else {
+        current_dpi(mouse, cache)?
+    };
+
+    for (index, col) in uncaptured {
+        if settings
+            .stages
+            .get(index as usize)
+            .map(|stage| stage.colour)
+            != Some(col)
+        {
+            return Err(StageColourError(col).into());
+        }
+    }
+
+    update.apply(&mut settings)?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+/// Changes only the given DPI settings, keeping the rest of the mouse's current settings as
+/// described in [`resolve_dpi`].
+///
+/// Returns the settings which were sent.
+pub fn update_dpi(
+    mouse: &(impl Transport + ?Sized),
+    update: &DpiUpdate,
+    cache: Option<&DpiCache>,
+) -> crate::Result<DpiSettings> {
+    let settings = resolve_dpi(mouse, update, cache)?;
+    set_dpi_settings(mouse, &settings, cache)?;
+    Ok(settings)
+}
+
+/// Makes the stage with the given index active, keeping the rest of the mouse's current settings.
+pub fn set_active_stage(
+    mouse: &(impl Transport + ?Sized),
+    index: u8,
+    cache: Option<&DpiCache>,
+) -> crate::Result<DpiSettings> {
+    let update = DpiUpdate {
+        active_stage: Some(index),
+        ..Default::default()
+    };
+
+    update_dpi(mouse, &update, cache)
+}
+
+/// Changes the DPI of a single stage, keeping the rest of the mouse's current settings.
+pub fn set_stage_dpi(
+    mouse: &(impl Transport + ?Sized),
+    index: u8,
+    dpi: u16,
+    cache: Option<&DpiCache>,
+) -> crate::Result<DpiSettings> {
+    let update = DpiUpdate {
+        stage_dpi: Some((index, dpi)),
+        ..Default::default()
+    };
+
+    update_dpi(mouse, &update, cache)
+}
+
+#[derive(Debug)]
+pub struct StageIndexError {
+    pub index: u8,
+    pub num_stages: usize,
+}
+
+impl std::fmt::Display for StageIndexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "DPI stage {} does not exist ({} stages set)",
+            self.index, self.num_stages
+        )
+    }
+}
+
+impl std::error::Error for StageIndexError {}
+
+/// A colour which isn't one of the [`StageColour`]s.
+#[derive(Debug)]
+pub struct StageColourError(pub Rgb);
+
+impl std::fmt::Display for StageColourError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is not a supported DPI stage colour (expected one of",
+            self.0
+        )?;
+        for colour in StageColour::ALL {
+            write!(f, " {}", colour.rgb())?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for StageColourError {}
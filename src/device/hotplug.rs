@@ -0,0 +1,160 @@
+use hidapi::HidApi;
+use serde::Serialize;
+
+use super::discovery::{KnownDevice, known_device, matching_interfaces};
+
+/// A change in which supported devices are connected.
+///
+/// Serialised as the event (`connected` or `disconnected`) along with the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", content = "device", rename_all = "lowercase")]
+pub enum HotplugEvent {
+    Connected(&'static KnownDevice),
+    Disconnected(&'static KnownDevice),
+}
+
+impl std::fmt::Display for HotplugEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HotplugEvent::Connected(d) => write!(f, "{} connected ({})", d.name, d.mode),
+            HotplugEvent::Disconnected(d) => write!(f, "{} disconnected ({})", d.name, d.mode),
+        }
+    }
+}
+
+/// Lists the supported devices which are currently connected.
+///
+/// Devices are identified by their VID/PID, so a mouse connected through both the cable and the
+/// dongle is listed once for each mode.
+pub trait Scanner {
+    fn scan(&mut self) -> crate::Result<Vec<&'static KnownDevice>>;
+}
+
+/// Scans connected devices using hidapi.
+///
+/// Works on every platform hidapi supports, but has to enumerate every HID device on each scan.
+pub struct HidApiScanner {
+    api: HidApi,
+}
+
+impl HidApiScanner {
+    pub fn new(api: HidApi) -> Self {
+        Self { api }
+    }
+}
+
+impl Scanner for HidApiScanner {
+    fn scan(&mut self) -> crate::Result<Vec<&'static KnownDevice>> {
+        self.api.refresh_devices()?;
+        Ok(matching_interfaces(&self.api)
+            .filter_map(|info| known_device(info.vendor_id(), info.product_id()))
+            .collect())
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use sysfs::SysfsScanner;
+
+#[cfg(target_os = "linux")]
+mod sysfs {
+    use std::{fs, io, path::PathBuf};
+
+    use hidapi::HidError;
+
+    use super::Scanner;
+    use crate::device::discovery::{KnownDevice, known_device};
+
+    /// Directory containing an entry for each hidraw device created by the kernel.
+    const HIDRAW_CLASS_DIR: &str = "/sys/class/hidraw";
+
+    /// Scans the hidraw devices listed in sysfs, which udev creates `/dev/hidrawN` nodes for.
+    ///
+    /// Devices aren't opened, so scanning doesn't require permission to access them.
+    #[derive(Debug)]
+    pub struct SysfsScanner {
+        dir: PathBuf,
+    }
+
+    impl SysfsScanner {
+        pub fn new() -> Self {
+            Self {
+                dir: PathBuf::from(HIDRAW_CLASS_DIR),
+            }
+        }
+    }
+
+    impl Default for SysfsScanner {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// Reads the VID and PID from the `HID_ID=<bus>:<vid>:<pid>` line of a uevent file.
+    fn parse_hid_id(uevent: &str) -> Option<(u16, u16)> {
+        let id = uevent.lines().find_map(|l| l.strip_prefix("HID_ID="))?;
+        let mut parts = id.split(':').skip(1);
+        let vid = u32::from_str_radix(parts.next()?, 16).ok()?;
+        let pid = u32::from_str_radix(parts.next()?, 16).ok()?;
+        Some((vid.try_into().ok()?, pid.try_into().ok()?))
+    }
+
+    impl Scanner for SysfsScanner {
+        fn scan(&mut self) -> crate::Result<Vec<&'static KnownDevice>> {
+            let entries = fs::read_dir(&self.dir).map_err(|error| HidError::IoError { error })?;
+
+            let mut devices = Vec::new();
+            for entry in entries {
+                let entry = entry.map_err(|error| HidError::IoError { error })?;
+                let uevent = match fs::read_to_string(entry.path().join("device/uevent")) {
+                    Ok(uevent) => uevent,
+                    // Removed since listing the directory
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(error) => return Err(HidError::IoError { error }.into()),
+                };
+
+                if let Some(device) = parse_hid_id(&uevent).and_then(|(v, p)| known_device(v, p)) {
+                    devices.push(device);
+                }
+            }
+
+            Ok(devices)
+        }
+    }
+}
+
+/// Reports supported devices being connected or disconnected, by comparing successive scans.
+pub struct Watcher<S> {
+    scanner: S,
+    connected: Vec<&'static KnownDevice>,
+}
+
+impl<S: Scanner> Watcher<S> {
+    /// Creates a watcher. Devices which are already connected are reported by the first poll.
+    pub fn new(scanner: S) -> Self {
+        Self {
+            scanner,
+            connected: Vec::new(),
+        }
+    }
+
+    /// Scans for devices, returning the changes since the previous scan.
+    pub fn poll(&mut self) -> crate::Result<Vec<HotplugEvent>> {
+        let mut scanned = self.scanner.scan()?;
+        scanned.sort_by_key(|d| (d.vid, d.pid));
+        scanned.dedup();
+
+        let disconnected = self
+            .connected
+            .iter()
+            .filter(|d| !scanned.contains(d))
+            .map(|d| HotplugEvent::Disconnected(d));
+        let connected = scanned
+            .iter()
+            .filter(|d| !self.connected.contains(d))
+            .map(|d| HotplugEvent::Connected(d));
+        let events = disconnected.chain(connected).collect();
+
+        self.connected = scanned;
+        Ok(events)
+    }
+}
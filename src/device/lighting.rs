@@ -1,96 +1,234 @@
-use std::error::Error;
-
-use hidapi::HidDevice;
-
-use crate::{rgb, util::{lighting_mode::LightingMode, rgb::Rgb}};
-
-use super::message::{MessageBuilder, default_header};
-
-// TODO: Move this to a LightingMode impl
-pub const GLORIOUS_PALETTE: [Rgb; 7] = [
-    rgb!(0xFF0000),
-    rgb!(0xFFC400),
-    rgb!(0xFBFF00),
-    rgb!(0x00FF33),
-    rgb!(0x00FBFF),
-    rgb!(0x0004FF),
-    rgb!(0xE600FF),
-];
-
-pub const SEAMLESS_BREATHING_PALETTE: [Rgb; 7] = GLORIOUS_PALETTE;
-
-/// Also requires one custom colour at the start
-pub const BREATHING_PALETTE: [Rgb; 5] = [
-    rgb!(0x0AFF7D),
-    rgb!(0xFF600A),
-    rgb!(0x0AFFE5),
-    rgb!(0xFF0AD4),
-    rgb!(0xFF0000),
-];
-
-pub const TAIL_PALETTE: [Rgb; 6] = [
-    rgb!(0xFFF60A),
-    rgb!(0x0AFF7D),
-    rgb!(0xFF600A),
-    rgb!(0x0AFFE5),
-    rgb!(0xFF0AD4),
-    rgb!(0xFF0000),
-];
-
-/// Also requires one custom colour at the start
-pub const RAVE_PALETTE: [Rgb; 1] = [rgb!(0xFFFF00)];
-
-pub const WAVE_PALETTE: [Rgb; 7] = [
-    rgb!(0xFFF60A),
-    rgb!(0x0AFF7D),
-    rgb!(0xFF600A),
-    rgb!(0x0AFFE5),
-    rgb!(0xFF0AD4),
-    rgb!(0x000000),
-    rgb!(0xFF0000),
-];
-
-const OPERATION_ID: u8 = 0x02;
-
-macro_rules! palette {
-    ($p:expr, $mb:ident $(, $c:expr)?) => {{
-        $($mb = $mb.push_block(&$c.bytes());)?
-        for c in $p {
-            $mb = $mb.push_block(&c.bytes());
+pub mod animate;
+
+use std::ops::RangeInclusive;
+
+use serde::Serialize;
+
+use crate::{
+    error::{ValidationError, check_range},
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+use super::{
+    message::{
+        DecodeError, DecodeResult, Message, MessageBuilder, MessageBuilderResult, MessageReader,
+        Report, default_header,
+    },
+    transport::Transport,
+};
+
+pub const OPERATION_ID: u8 = 0x02;
+
+/// Lighting brightness.
+pub const BRIGHTNESS_RANGE: RangeInclusive<u8> = 0x00..=0x14;
+
+/// Lighting rate. A rate of 0 probably doesn't work.
+pub const RATE_RANGE: RangeInclusive<u8> = 0x01..=0x14;
+
+pub const NUM_REPORTS: u8 = 3;
+
+/// Number of bytes in lighting headers, which include the lighting mode.
+const HEADER_LEN: usize = 6;
+
+fn header(mode_id: u8, index: u8) -> Vec<u8> {
+    let mut header = default_header(OPERATION_ID, index).to_vec();
+    header.push(mode_id);
+    header
+}
+
+/// A lighting effect, along with its brightness, rate and colours.
+///
+/// The mouse keeps a separate brightness and rate for when it's connected wirelessly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LightingSettings {
+    #[serde(flatten)]
+    pub mode: LightingMode,
+    /// Ranges from 0 to 20 (inclusive)
+    pub brightness: u8,
+    /// Ranges from 1 to 20 (inclusive)
+    pub rate: u8,
+    /// Ranges from 0 to 20 (inclusive)
+    pub wireless_brightness: u8,
+    /// Ranges from 1 to 20 (inclusive)
+    pub wireless_rate: u8,
+    pub colours: Vec<Rgb>,
+}
+
+impl LightingSettings {
+    /// Creates lighting settings using the colours Glorious Core sends for the given mode.
+    ///
+    /// The brightness and rate are used both when wired and when wireless, as Glorious Core does.
+    pub fn new(mode: LightingMode, brightness: u8, rate: u8) -> Self {
+        Self {
+            mode,
+            brightness,
+            rate,
+            wireless_brightness: brightness,
+            wireless_rate: rate,
+            colours: mode.default_colours(),
+        }
+    }
+
+    /// Sets a different brightness and rate for when the mouse is connected wirelessly.
+    pub fn with_wireless(mut self, brightness: u8, rate: u8) -> Self {
+        self.wireless_brightness = brightness;
+        self.wireless_rate = rate;
+        self
+    }
+
+    /// Replaces the colours Glorious Core sends with a custom palette.
+    ///
+    /// Glorious Core only allows the first colour to be changed, which is the mode's custom colour
+    /// if it has one, so that is updated to match. The number of colours must match
+    /// [`LightingMode::num_colours`], which is checked by [`LightingSettings::validate`].
+    pub fn with_colours(mut self, colours: Vec<Rgb>) -> Self {
+        if let Some(col) = colours.first() {
+            self.mode = self.mode.with_custom_colour(*col);
+        }
+
+        self.colours = colours;
+        self
+    }
+
+    /// Checks that all settings are within their allowed ranges, and that the number of colours
+    /// matches the lighting mode.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        check_range("brightness", self.brightness, BRIGHTNESS_RANGE)?;
+        check_range("rate", self.rate, RATE_RANGE)?;
+        check_range(
+            "wireless brightness",
+            self.wireless_brightness,
+            BRIGHTNESS_RANGE,
+        )?;
+        check_range("wireless rate", self.wireless_rate, RATE_RANGE)?;
+
+        let expected = self.mode.num_colours() as usize;
+        if self.colours.len() != expected {
+            return Err(ValidationError::ColourCount {
+                expected,
+                found: self.colours.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Builds the message setting these settings.
+    pub fn message(&self) -> MessageBuilderResult<Message> {
+        let mode_id = self.mode.mode_id();
+        let mut mb = MessageBuilder::new(OPERATION_ID, NUM_REPORTS)
+            .with_header(|i| header(mode_id, i))
+            .push(self.rate)
+            .push(self.brightness)
+            .push(self.mode.num_colours())
+            .push(self.wireless_rate)
+            .push(self.wireless_brightness);
+
+        for col in &self.colours {
+            mb = mb.push_block(&col.bytes());
+        }
+
+        mb.build()
+    }
+
+    /// Decodes lighting settings from the reports of a lighting message.
+    pub fn from_reports(reports: &[Report]) -> DecodeResult<Self> {
+        let mode_id = reports
+            .first()
+            .and_then(|r| r.data().get(HEADER_LEN - 1).copied())
+            .ok_or(DecodeError::DataLenError { report_i: 0 })?;
+        let mut reader = MessageReader::with_header(reports, |i| header(mode_id, i))?;
+
+        let rate = reader.read()?;
+        let brightness = reader.read()?;
+        let num_colours = reader.read()?;
+        let wireless_rate = reader.read()?;
+        let wireless_brightness = reader.read()?;
+
+        let mut colours = Vec::new();
+        for _ in 0..num_colours {
+            let block = reader.read_block(3)?;
+            colours.push(Rgb::new(block[0], block[1], block[2]));
+        }
+
+        let col = colours.first().copied().unwrap_or_default();
+        let mode = LightingMode::from_id(mode_id, col).ok_or(DecodeError::ValueError {
+            field: "lighting mode",
+            value: mode_id.into(),
+        })?;
+
+        Ok(Self {
+            mode,
+            brightness,
+            rate,
+            wireless_brightness,
+            wireless_rate,
+            colours,
+        })
+    }
+}
+
+impl std::fmt::Display for LightingSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Lighting mode: {}", self.mode)?;
+        writeln!(f, "Brightness: {}", self.brightness)?;
+        write!(f, "Rate: {}", self.rate)?;
+
+        if (self.wireless_brightness, self.wireless_rate) != (self.brightness, self.rate) {
+            write!(f, "\nWireless brightness: {}", self.wireless_brightness)?;
+            write!(f, "\nWireless rate: {}", self.wireless_rate)?;
+        }
+
+        if !self.colours.is_empty() {
+            write!(f, "\nColours:")?;
+            for col in &self.colours {
+                write!(f, " {col}")?;
+            }
         }
-    }};
+
+        Ok(())
+    }
+}
+
+/// Returns a palette for the given mode which is black except for `colour` at `position`.
+///
+/// Sending the palette for each position in turn shows which positions the firmware actually uses,
+/// rather than its own colours.
+pub fn probe_palette(mode: LightingMode, position: usize, colour: Rgb) -> Vec<Rgb> {
+    let mut colours = vec![Rgb::default(); mode.num_colours() as usize];
+    if let Some(col) = colours.get_mut(position) {
+        *col = colour;
+    }
+
+    colours
 }
 
+/// Sets the lighting, using the same brightness and rate when wired and wireless.
 pub fn set_lighting(
-    mouse: &HidDevice,
+    mouse: &(impl Transport + ?Sized),
     brightness: u8,
     rate: u8,
     mode: LightingMode,
-) -> Result<(), Box<dyn Error>> {
-    let mut mb = MessageBuilder::new(OPERATION_ID, 3)
-        .with_header(|i| {
-            let mut header = default_header(0x02, i).to_vec();
-            header.push(mode.mode_id());
-            header
-        })
-        .push(rate)
-        .push(brightness)
-        .push(mode.num_colours())
-        .push(rate)
-        .push(brightness);
-
-    match mode {
-        LightingMode::Off => (),
-        LightingMode::Glorious => palette!(GLORIOUS_PALETTE, mb),
-        LightingMode::SeamlessBreathing => palette!(SEAMLESS_BREATHING_PALETTE, mb),
-        LightingMode::Breathing { col } => palette!(BREATHING_PALETTE, mb, col),
-        LightingMode::SingleColour { col } => mb = mb.push_block(&col.bytes()),
-        LightingMode::BreathingSingleColour { col } => mb = mb.push_block(&col.bytes()),
-        LightingMode::Tail => palette!(TAIL_PALETTE, mb),
-        LightingMode::Rave { col } => palette!(RAVE_PALETTE, mb, col),
-        LightingMode::Wave => palette!(WAVE_PALETTE, mb),
-    }
+) -> crate::Result<()> {
+    set_lighting_settings(mouse, &LightingSettings::new(mode, brightness, rate))
+}
 
-    mb.build()?.send(mouse)?;
+pub fn set_lighting_settings(
+    mouse: &(impl Transport + ?Sized),
+    settings: &LightingSettings,
+) -> crate::Result<()> {
+    settings.validate()?;
+    settings.message()?.send(mouse)?;
     Ok(())
 }
+
+/// Reads the current lighting settings from the mouse.
+///
+/// The request is a guess, see [`read_header`](super::message::read_header).
+#[cfg(feature = "unverified-reads")]
+pub fn get_lighting(mouse: &(impl Transport + ?Sized)) -> crate::Result<LightingSettings> {
+    let replies = MessageBuilder::read_request(OPERATION_ID, NUM_REPORTS)
+        .build()?
+        .query(mouse)?;
+    Ok(LightingSettings::from_reports(&replies)?)
+}
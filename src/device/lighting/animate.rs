@@ -0,0 +1,327 @@
+use std::{
+    fs, io,
+    path::Path,
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{
+    device::transport::Transport,
+    util::{
+        lighting_mode::LightingMode,
+        rgb::{ParseRgbError, Rgb},
+    },
+};
+
+use super::{LightingSettings, NUM_REPORTS, RATE_RANGE, set_lighting_settings};
+
+/// A lighting effect drawn by the host, as a colour which changes over time.
+pub trait Effect {
+    /// Returns the colour to show at `t` since the start of the effect, or `None` once it has
+    /// finished.
+    fn colour(&mut self, t: Duration) -> Option<Rgb>;
+}
+
+/// Fades from one colour to another and back again, once per period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub from: Rgb,
+    pub to: Rgb,
+    pub period: Duration,
+}
+
+impl Effect for Gradient {
+    fn colour(&mut self, t: Duration) -> Option<Rgb> {
+        let phase = (t.as_secs_f32() / self.period.as_secs_f32()).fract();
+        Some(self.from.lerp(self.to, 1.0 - (2.0 * phase - 1.0).abs()))
+    }
+}
+
+/// Fades through each colour of a palette in turn, repeating forever.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    pub palette: Vec<Rgb>,
+    /// Time taken to fade from one colour to the next.
+    pub step: Duration,
+}
+
+impl Effect for Cycle {
+    fn colour(&mut self, t: Duration) -> Option<Rgb> {
+        let steps = t.as_secs_f32() / self.step.as_secs_f32();
+        let i = steps as usize % self.palette.len().max(1);
+        let next = self.palette.get(i + 1).or(self.palette.first())?;
+        Some(self.palette.get(i)?.lerp(*next, steps.fract()))
+    }
+}
+
+/// Shows an idle colour, flashing another colour and fading back whenever it's triggered, e.g. by a
+/// notification.
+#[derive(Debug)]
+pub struct Pulse {
+    pub idle: Rgb,
+    pub colour: Rgb,
+    /// Time taken to fade back to the idle colour.
+    pub fade: Duration,
+    triggered: Arc<AtomicBool>,
+    started: Option<Duration>,
+}
+
+/// Triggers a [`Pulse`], possibly from another thread.
+#[derive(Debug, Clone)]
+pub struct PulseTrigger(Arc<AtomicBool>);
+
+impl PulseTrigger {
+    /// Starts a pulse at the next frame, restarting it if one is already showing.
+    pub fn pulse(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Pulse {
+    pub fn new(idle: Rgb, colour: Rgb, fade: Duration) -> Self {
+        Self {
+            idle,
+            colour,
+            fade,
+            triggered: Arc::default(),
+            started: None,
+        }
+    }
+
+    pub fn trigger(&self) -> PulseTrigger {
+        PulseTrigger(Arc::clone(&self.triggered))
+    }
+}
+
+impl Effect for Pulse {
+    fn colour(&mut self, t: Duration) -> Option<Rgb> {
+        if self.triggered.swap(false, Ordering::Relaxed) {
+            self.started = Some(t);
+        }
+
+        let elapsed = self.started.map_or(self.fade, |start| t - start);
+        if elapsed >= self.fade {
+            self.started = None;
+            return Some(self.idle);
+        }
+
+        let progress = elapsed.as_secs_f32() / self.fade.as_secs_f32();
+        Some(self.colour.lerp(self.idle, progress))
+    }
+}
+
+/// Errors from reading a keyframe file.
+#[derive(Debug)]
+pub enum KeyframeError {
+    Io(io::Error),
+    TimeError {
+        line: usize,
+    },
+    ColourError {
+        line: usize,
+        error: ParseRgbError,
+    },
+    /// Keyframes must be in order of time.
+    OrderError {
+        line: usize,
+    },
+    EmptyError,
+}
+
+impl std::fmt::Display for KeyframeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyframeError::Io(e) => write!(f, "Couldn't read keyframes: {e}"),
+            KeyframeError::TimeError { line } => {
+                write!(f, "Invalid time of the keyframe on line {line}")
+            }
+            KeyframeError::ColourError { line, error } => {
+                write!(f, "Invalid colour of the keyframe on line {line}: {error}")
+            }
+            KeyframeError::OrderError { line } => {
+                write!(
+                    f,
+                    "The keyframe on line {line} is earlier than the one before it"
+                )
+            }
+            KeyframeError::EmptyError => write!(f, "No keyframes were given"),
+        }
+    }
+}
+
+impl std::error::Error for KeyframeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KeyframeError::Io(e) => Some(e),
+            KeyframeError::ColourError { line: _, error } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for KeyframeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Colours at given times, which are faded between.
+///
+/// Keyframe files have a keyframe on each line, given as the time in seconds followed by the
+/// colour. Blank lines and lines starting with `//` are ignored. Example:
+/// ```
+/// # use std::time::Duration;
+/// # use i2control::{device::lighting::animate::{Effect, Keyframes}, util::rgb::Rgb};
+/// let mut keyframes: Keyframes = "
+///     // Fade from red to blue, then hold blue
+///     0   red
+///     1.5 #0000FF
+///     3   rgb(0, 0, 255)
+/// ".parse().unwrap();
+/// assert_eq!(keyframes.colour(Duration::from_millis(750)), Some(Rgb::new(128, 0, 128)));
+/// assert_eq!(keyframes.colour(Duration::from_secs(4)), None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframes {
+    frames: Vec<(Duration, Rgb)>,
+    /// Whether to start again from the first keyframe after the last one.
+    pub looping: bool,
+}
+
+impl Keyframes {
+    /// Reads keyframes from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, KeyframeError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Returns the time of the last keyframe.
+    pub fn length(&self) -> Duration {
+        self.frames.last().map_or(Duration::ZERO, |(t, _)| *t)
+    }
+}
+
+impl FromStr for Keyframes {
+    type Err = KeyframeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut frames: Vec<(Duration, Rgb)> = Vec::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let (line, line_no) = (line.trim(), i + 1);
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let time_error = KeyframeError::TimeError { line: line_no };
+            let (time, colour) = line.split_once(char::is_whitespace).ok_or(time_error)?;
+            let time = time
+                .parse()
+                .ok()
+                .and_then(|t| Duration::try_from_secs_f32(t).ok())
+                .ok_or(KeyframeError::TimeError { line: line_no })?;
+            let colour = colour
+                .trim()
+                .parse()
+                .map_err(|error| KeyframeError::ColourError {
+                    line: line_no,
+                    error,
+                })?;
+
+            if frames.last().is_some_and(|(last, _)| time < *last) {
+                return Err(KeyframeError::OrderError { line: line_no });
+            }
+
+            frames.push((time, colour));
+        }
+
+        if frames.is_empty() {
+            return Err(KeyframeError::EmptyError);
+        }
+
+        Ok(Self {
+            frames,
+            looping: false,
+        })
+    }
+}
+
+impl Effect for Keyframes {
+    fn colour(&mut self, t: Duration) -> Option<Rgb> {
+        let length = self.length();
+        let t = match (self.looping, length.is_zero()) {
+            (true, false) => Duration::from_secs_f32(t.as_secs_f32() % length.as_secs_f32()),
+            _ if t > length => return None,
+            _ => t,
+        };
+
+        let next = self.frames.iter().position(|(time, _)| *time > t);
+        let Some(next) = next.filter(|i| *i > 0) else {
+            let (_, colour) = self.frames[next.unwrap_or(self.frames.len() - 1)];
+            return Some(colour);
+        };
+
+        let ((start, from), (end, to)) = (self.frames[next - 1], self.frames[next]);
+        Some(from.lerp(to, (t - start).as_secs_f32() / (end - start).as_secs_f32()))
+    }
+}
+
+/// Returns the time between frames when animating at `fps`.
+///
+/// Each frame is a lighting message, so frames are never closer together than the transport allows
+/// for all of its reports.
+pub fn frame_interval(mouse: &(impl Transport + ?Sized), fps: f32) -> Duration {
+    let min = mouse.report_interval() * NUM_REPORTS as u32;
+    Duration::try_from_secs_f32(1.0 / fps).map_or(min, |interval| interval.max(min))
+}
+
+/// Plays an effect by sending a single colour lighting message for each frame.
+///
+/// Frames are taken at fixed steps of [`frame_interval`], so the same effect always sends the same
+/// messages. Frames which are the same colour as the previous one aren't sent. Plays until the
+/// effect finishes, or for `duration` if given. Returns the number of messages sent.
+pub fn play(
+    mouse: &(impl Transport + ?Sized),
+    effect: &mut (impl Effect + ?Sized),
+    brightness: u8,
+    fps: f32,
+    duration: Option<Duration>,
+) -> crate::Result<usize> {
+    let interval = frame_interval(mouse, fps);
+    debug!("Animating with {interval:?} between frames");
+
+    let start = Instant::now();
+    let mut last = None;
+    let mut sent = 0;
+
+    for frame in 0.. {
+        let t = interval * frame;
+        if duration.is_some_and(|d| t >= d) {
+            break;
+        }
+
+        let Some(col) = effect.colour(t) else {
+            break;
+        };
+
+        if let Some(wait) = (start + t).checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+
+        if last != Some(col) {
+            let mode = LightingMode::SingleColour { col };
+            let settings = LightingSettings::new(mode, brightness, *RATE_RANGE.start());
+            set_lighting_settings(mouse, &settings)?;
+            last = Some(col);
+            sent += 1;
+        }
+    }
+
+    Ok(sent)
+}
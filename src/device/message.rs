@@ -1,6 +1,8 @@
-use std::{thread, time::Duration};
+use std::thread;
 
-use hidapi::{HidDevice, HidResult};
+use log::trace;
+
+use super::transport::Transport;
 
 /// ID required as the first byte of all HID Reports.
 pub const REPORT_ID: u8 = 0x03;
@@ -9,20 +11,32 @@ pub const REPORT_ID: u8 = 0x03;
 pub const REPORT_LEN: usize = 16;
 
 /// Number of bytes required for the header of each report.
-const DEFAULT_HEADER_LEN: usize = 5;
+pub const DEFAULT_HEADER_LEN: usize = 5;
 
-/// Time to wait between each report of a message.
-///
-/// DPI operations seem to be particularly sensitive to this.
-/// Assuming Core operates in a similar way, it seems like it uses a 150ms interval.
-const REPORT_INTERVAL: Duration = Duration::from_millis(150);
+/// Time to wait for a reply to arrive as an input report, if the mouse doesn't provide it as a
+/// feature report.
+#[cfg(feature = "unverified-reads")]
+const REPLY_TIMEOUT_MS: i32 = 500;
 
 pub const fn default_header(operation: u8, index: u8) -> [u8; DEFAULT_HEADER_LEN] {
     [REPORT_ID, operation, 0xFB, index, 0x01]
 }
 
+/// Header used to request the current settings of an operation from the mouse.
+///
+/// This is assumed to be the default header with the final byte cleared. The mouse replies to each
+/// report with the corresponding report of the message that would set its current settings.
+///
+/// No read has been captured from Glorious Core, so this is only built with the `unverified-reads`
+/// feature. The guess can't work for every operation: timeout messages only use the first 3 bytes
+/// of the header, so a read request for the timeout would set it to 0 instead.
+#[cfg(feature = "unverified-reads")]
+pub const fn read_header(operation: u8, index: u8) -> [u8; DEFAULT_HEADER_LEN] {
+    [REPORT_ID, operation, 0xFB, index, 0x00]
+}
+
 #[derive(Clone, PartialEq, Eq)]
-struct Report {
+pub struct Report {
     data: Vec<u8>,
 }
 
@@ -31,14 +45,62 @@ impl Report {
         Self { data }
     }
 
+    /// Creates a report from raw bytes, e.g. from captured traffic.
+    ///
+    /// Only the first [`REPORT_LEN`] bytes are kept, since Glorious Core pads reports to 64 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> DecodeResult<Self> {
+        if bytes.len() < REPORT_LEN {
+            return Err(DecodeError::ReportLenError { len: bytes.len() });
+        }
+
+        if bytes[0] != REPORT_ID {
+            return Err(DecodeError::ReportIdError {
+                report_id: bytes[0],
+            });
+        }
+
+        Ok(Self::new(bytes[..REPORT_LEN].to_vec()))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the operation ID from the report header.
+    pub fn operation(&self) -> u8 {
+        self.data[1]
+    }
+
     /// Sends the feature report to the given mouse.
     ///
-    /// Sleeps for [`REPORT_INTERVAL`] after sending to allow time to process requests.
-    fn send(&self, mouse: &HidDevice) -> HidResult<()> {
+    /// Sleeps for the transport's report interval after sending to allow time to process requests.
+    fn send(&self, mouse: &(impl Transport + ?Sized)) -> crate::Result<()> {
         mouse.send_feature_report(self.data.as_slice())?;
-        thread::sleep(REPORT_INTERVAL);
+        trace!("Sent {:02X?}", self.data);
+        thread::sleep(mouse.report_interval());
         Ok(())
     }
+
+    /// Reads the mouse's reply to a report for the given operation.
+    ///
+    /// The reply is read as a feature report. If that doesn't contain a reply to the operation, the
+    /// next input report is used instead.
+    #[cfg(feature = "unverified-reads")]
+    fn receive(mouse: &(impl Transport + ?Sized), operation: u8) -> crate::Result<Self> {
+        let mut data = vec![0x00; REPORT_LEN];
+        data[0] = REPORT_ID;
+        mouse.get_feature_report(&mut data)?;
+
+        if data[1] != operation {
+            trace!("Feature report {data:02X?} isn't a reply, reading an input report");
+            data.fill(0x00);
+            mouse.read_timeout(&mut data, REPLY_TIMEOUT_MS)?;
+        }
+
+        trace!("Received {data:02X?}");
+
+        Ok(Self::new(data))
+    }
 }
 
 impl std::fmt::Debug for Report {
@@ -61,16 +123,38 @@ impl Message {
         Self { reports }
     }
 
+    pub fn reports(&self) -> &[Report] {
+        &self.reports
+    }
+
+    /// Returns the bytes of each report, as they're sent.
+    pub fn to_bytes(&self) -> Vec<Vec<u8>> {
+        self.reports.iter().map(|r| r.data().to_vec()).collect()
+    }
+
     /// Sends each report in the current message.
     ///
-    /// Sleeps for [`REPORT_INTERVAL`] after sending each report to allow time to process.
-    pub fn send(self, mouse: &HidDevice) -> HidResult<()> {
+    /// Sleeps for the transport's report interval after sending each report to allow time to
+    /// process.
+    pub fn send(self, mouse: &(impl Transport + ?Sized)) -> crate::Result<()> {
         for report in self.reports {
             report.send(mouse)?;
         }
 
         Ok(())
     }
+
+    /// Sends each report in the current message, reading back the mouse's reply after each one.
+    #[cfg(feature = "unverified-reads")]
+    pub fn query(self, mouse: &(impl Transport + ?Sized)) -> crate::Result<Vec<Report>> {
+        let mut replies = Vec::new();
+        for report in self.reports {
+            report.send(mouse)?;
+            replies.push(Report::receive(mouse, report.operation())?);
+        }
+
+        Ok(replies)
+    }
 }
 
 pub struct MessageBuilder<'header> {
@@ -88,6 +172,15 @@ impl<'header> MessageBuilder<'header> {
         }
     }
 
+    /// Creates a builder for a message requesting the current settings of an operation.
+    ///
+    /// See [`read_header`].
+    #[cfg(feature = "unverified-reads")]
+    pub fn read_request(operation_id: u8, num_reports: u8) -> Self {
+        Self::new(operation_id, num_reports)
+            .with_header(move |i| read_header(operation_id, i).to_vec())
+    }
+
     pub fn with_header(mut self, header_fn: impl FnMut(u8) -> Vec<u8> + 'header) -> Self {
         self.header_fn = Box::new(header_fn);
         self
@@ -134,15 +227,14 @@ impl<'header> MessageBuilder<'header> {
                 }
 
                 i += 1;
-                continue;
-            }
 
-            if report.len() + block.len() > REPORT_LEN {
-                // Current block too long
-                return Err(MessageBuilderError::BlockLenError {
-                    block_i,
-                    block_len: block.len(),
-                });
+                if report.len() + block.len() > REPORT_LEN {
+                    // Current block too long
+                    return Err(MessageBuilderError::BlockLenError {
+                        block_i,
+                        block_len: block.len(),
+                    });
+                }
             }
 
             report.extend(block); // Push block to current report
@@ -170,7 +262,7 @@ impl<'header> MessageBuilder<'header> {
     }
 }
 
-type MessageBuilderResult<T> = Result<T, MessageBuilderError>;
+pub type MessageBuilderResult<T> = Result<T, MessageBuilderError>;
 
 #[derive(Debug)]
 pub enum MessageBuilderError {
@@ -196,3 +288,120 @@ impl std::fmt::Display for MessageBuilderError {
 }
 
 impl std::error::Error for MessageBuilderError {}
+
+/// Reads blocks back out of the reports of a message, following the same layout as
+/// [`MessageBuilder`].
+pub struct MessageReader<'a> {
+    reports: &'a [Report],
+    header_lens: Vec<usize>,
+    report_i: usize,
+    pos: usize,
+}
+
+impl<'a> MessageReader<'a> {
+    /// Creates a reader for a message with default headers.
+    pub fn new(reports: &'a [Report], operation_id: u8) -> DecodeResult<Self> {
+        Self::with_header(reports, |i| default_header(operation_id, i).to_vec())
+    }
+
+    /// Creates a reader, checking that each report begins with the header given by `header_fn`.
+    pub fn with_header(
+        reports: &'a [Report],
+        mut header_fn: impl FnMut(u8) -> Vec<u8>,
+    ) -> DecodeResult<Self> {
+        let mut header_lens = Vec::new();
+        for (report_i, report) in reports.iter().enumerate() {
+            let header = header_fn(report_i as u8);
+            let data = report.data();
+            if data.len() != REPORT_LEN || !data.starts_with(&header) {
+                return Err(DecodeError::HeaderError {
+                    report_i,
+                    header: data[..header.len().min(data.len())].to_vec(),
+                });
+            }
+
+            header_lens.push(header.len());
+        }
+
+        Ok(Self {
+            reports,
+            pos: header_lens.first().copied().unwrap_or(REPORT_LEN),
+            header_lens,
+            report_i: 0,
+        })
+    }
+
+    pub fn read(&mut self) -> DecodeResult<u8> {
+        Ok(self.read_block(1)?[0])
+    }
+
+    /// Reads a block of `len` bytes.
+    ///
+    /// If there isn't enough room left in the current report for a block of this length, then it
+    /// is read from the start of the data section of the next report.
+    pub fn read_block(&mut self, len: usize) -> DecodeResult<&'a [u8]> {
+        if self.pos + len > REPORT_LEN {
+            self.report_i += 1;
+            match self.header_lens.get(self.report_i) {
+                Some(header_len) => self.pos = *header_len,
+                None => {
+                    return Err(DecodeError::DataLenError {
+                        report_i: self.report_i,
+                    });
+                }
+            }
+
+            if self.pos + len > REPORT_LEN {
+                return Err(DecodeError::BlockLenError { block_len: len });
+            }
+        }
+
+        let block = &self.reports[self.report_i].data()[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(block)
+    }
+}
+
+pub type DecodeResult<T> = Result<T, DecodeError>;
+
+/// Errors from interpreting reports, whether read back from a mouse or captured.
+#[derive(Debug)]
+pub enum DecodeError {
+    ReportLenError { len: usize },
+    ReportIdError { report_id: u8 },
+    OperationError { operation: u8 },
+    DataLenError { report_i: usize },
+    HeaderError { report_i: usize, header: Vec<u8> },
+    BlockLenError { block_len: usize },
+    ValueError { field: &'static str, value: u16 },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::ReportLenError { len } => {
+                write!(f, "Report too short ({len} bytes, expected {REPORT_LEN})")
+            }
+            DecodeError::ReportIdError { report_id } => {
+                write!(f, "Unexpected report ID ({report_id:#04X})")
+            }
+            DecodeError::OperationError { operation } => {
+                write!(f, "Unknown operation ({operation:#04X})")
+            }
+            DecodeError::DataLenError { report_i } => {
+                write!(f, "Message ended early, report {report_i} is missing")
+            }
+            DecodeError::HeaderError { report_i, header } => {
+                write!(f, "Unexpected header for report {report_i} ({header:02X?})")
+            }
+            DecodeError::BlockLenError { block_len } => {
+                write!(f, "Block too long ({block_len})")
+            }
+            DecodeError::ValueError { field, value } => {
+                write!(f, "Invalid value for {field} ({value:#04X})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
@@ -1,4 +1,9 @@
+pub mod battery;
+pub mod decode;
+pub mod discovery;
 pub mod dpi;
+pub mod hotplug;
 pub mod lighting;
 pub mod message;
 pub mod timeout;
+pub mod transport;
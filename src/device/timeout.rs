@@ -1,16 +1,129 @@
-use std::error::Error;
+use std::ops::RangeInclusive;
 
-use hidapi::HidDevice;
+use serde::{Deserialize, Serialize};
 
-use super::message::{MessageBuilder, default_header};
+use crate::error::{ValidationError, check_range};
 
-const OPERATION_ID: u8 = 0x06;
+use super::{
+    message::{
+        DecodeResult, Message, MessageBuilder, MessageBuilderResult, MessageReader, Report,
+        default_header,
+    },
+    transport::Transport,
+};
 
-pub fn set_timeout(mouse: &HidDevice, timeout: Option<u8>) -> Result<(), Box<dyn Error>> {
-    let mb = MessageBuilder::new(OPERATION_ID, 1)
-        .with_header(|_| default_header(0x06, 0x00)[..3].to_vec())
-        .push(timeout.unwrap_or(0xFF)); // Timeout disabled is signalled by sending 0xFF
+pub const OPERATION_ID: u8 = 0x06;
 
-    mb.build()?.send(mouse)?;
+pub const NUM_REPORTS: u8 = 1;
+
+/// Minutes of inactivity before sleep.
+///
+/// A value of 100 is rendered as infinity in Glorious Core.
+pub const MINUTES_RANGE: RangeInclusive<u8> = 0x00..=0x64;
+
+/// Timeout disabled is signalled by sending 0xFF
+const DISABLED: u8 = 0xFF;
+
+/// Name of a disabled timeout in config files.
+const DISABLED_NAME: &str = "disabled";
+
+/// Global timeout headers only include the first 3 bytes of a regular header.
+fn header() -> Vec<u8> {
+    default_header(OPERATION_ID, 0x00)[..3].to_vec()
+}
+
+/// Global inactivity timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    Disabled,
+    /// Minutes of inactivity before sleep (0 to 100 inclusive).
+    Minutes(u8),
+}
+
+impl Timeout {
+    /// Checks that the timeout is within its allowed range.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self {
+            Timeout::Disabled => Ok(()),
+            Timeout::Minutes(minutes) => check_range("timeout", *minutes, MINUTES_RANGE),
+        }
+    }
+
+    /// Builds the message setting this timeout.
+    pub fn message(&self) -> MessageBuilderResult<Message> {
+        let timeout = match self {
+            Timeout::Disabled => DISABLED,
+            Timeout::Minutes(minutes) => *minutes,
+        };
+
+        MessageBuilder::new(OPERATION_ID, NUM_REPORTS)
+            .with_header(|_| header())
+            .push(timeout)
+            .build()
+    }
+
+    /// Decodes the timeout from a timeout message.
+    pub fn from_reports(reports: &[Report]) -> DecodeResult<Self> {
+        let mut reader = MessageReader::with_header(reports, |_| header())?;
+        Ok(match reader.read()? {
+            DISABLED => Timeout::Disabled,
+            minutes => Timeout::Minutes(minutes),
+        })
+    }
+}
+
+impl From<Option<u8>> for Timeout {
+    fn from(minutes: Option<u8>) -> Self {
+        minutes.map_or(Timeout::Disabled, Timeout::Minutes)
+    }
+}
+
+/// Timeouts are serialised as a number of minutes, or as the string "disabled".
+impl Serialize for Timeout {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Timeout::Disabled => serializer.serialize_str(DISABLED_NAME),
+            Timeout::Minutes(minutes) => serializer.serialize_u8(*minutes),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Timeout {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Minutes(u8),
+            Name(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Minutes(minutes) => Ok(Timeout::Minutes(minutes)),
+            Repr::Name(name) if name == DISABLED_NAME => Ok(Timeout::Disabled),
+            Repr::Name(name) => Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Str(&name),
+                &"a number of minutes or \"disabled\"",
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Timeout::Disabled => write!(f, "Timeout: disabled"),
+            Timeout::Minutes(minutes) => write!(f, "Timeout: {minutes} minutes"),
+        }
+    }
+}
+
+/// Sets the global inactivity timeout, given as a [`Timeout`] or as optional minutes.
+pub fn set_timeout(
+    mouse: &(impl Transport + ?Sized),
+    timeout: impl Into<Timeout>,
+) -> crate::Result<()> {
+    let timeout = timeout.into();
+    timeout.validate()?;
+    timeout.message()?.send(mouse)?;
     Ok(())
 }
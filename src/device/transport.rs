@@ -0,0 +1,209 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use hidapi::{HidDevice, HidError};
+
+/// Time to wait between each report of a message.
+///
+/// DPI operations seem to be particularly sensitive to this.
+/// Assuming Core operates in a similar way, it seems like it uses a 150ms interval.
+pub const REPORT_INTERVAL: Duration = Duration::from_millis(150);
+
+/// A connection to a mouse, over which reports can be sent and received.
+pub trait Transport {
+    fn send_feature_report(&self, data: &[u8]) -> crate::Result<()>;
+
+    /// Reads a feature report into `buf`, whose first byte must be set to the report ID.
+    ///
+    /// Returns the number of bytes read.
+    fn get_feature_report(&self, buf: &mut [u8]) -> crate::Result<usize>;
+
+    /// Reads an input report into `buf`, waiting for at most `timeout_ms` milliseconds.
+    ///
+    /// Returns the number of bytes read, which is 0 if the timeout expired.
+    fn read_timeout(&self, buf: &mut [u8], timeout_ms: i32) -> crate::Result<usize>;
+
+    /// Time to wait after sending each report to allow the mouse to process it.
+    fn report_interval(&self) -> Duration {
+        REPORT_INTERVAL
+    }
+}
+
+impl Transport for HidDevice {
+    fn send_feature_report(&self, data: &[u8]) -> crate::Result<()> {
+        Ok(HidDevice::send_feature_report(self, data)?)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> crate::Result<usize> {
+        Ok(HidDevice::get_feature_report(self, buf)?)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout_ms: i32) -> crate::Result<usize> {
+        Ok(HidDevice::read_timeout(self, buf, timeout_ms)?)
+    }
+}
+
+/// An in-memory transport, which records every report sent to it and replies with queued reports.
+///
+/// Doesn't wait between reports.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    sent: Mutex<Vec<Vec<u8>>>,
+    replies: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a recorder which replies with the given reports, in order.
+    pub fn with_replies(replies: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        Self {
+            sent: Mutex::default(),
+            replies: Mutex::new(replies.into_iter().collect()),
+        }
+    }
+
+    /// Queues a report to reply with.
+    pub fn push_reply(&self, reply: Vec<u8>) {
+        self.replies.lock().unwrap().push_back(reply);
+    }
+
+    /// Returns all reports sent so far.
+    pub fn sent(&self) -> Vec<Vec<u8>> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Returns and clears all reports sent so far.
+    pub fn take_sent(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.sent.lock().unwrap())
+    }
+
+    fn pop_reply(&self, buf: &mut [u8]) -> Option<usize> {
+        let reply = self.replies.lock().unwrap().pop_front()?;
+        let len = reply.len().min(buf.len());
+        buf[..len].copy_from_slice(&reply[..len]);
+        Some(len)
+    }
+}
+
+impl Transport for Recorder {
+    fn send_feature_report(&self, data: &[u8]) -> crate::Result<()> {
+        self.sent.lock().unwrap().push(data.to_vec());
+        Ok(())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> crate::Result<usize> {
+        self.pop_reply(buf).ok_or_else(|| {
+            HidError::HidApiError {
+                message: "No reply queued".to_string(),
+            }
+            .into()
+        })
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], _timeout_ms: i32) -> crate::Result<usize> {
+        Ok(self.pop_reply(buf).unwrap_or(0))
+    }
+
+    fn report_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use hidraw::Hidraw;
+
+#[cfg(target_os = "linux")]
+mod hidraw {
+    use std::{
+        fs::{File, OpenOptions},
+        io::{self, Read},
+        os::fd::AsRawFd,
+        path::Path,
+    };
+
+    use hidapi::HidError;
+
+    use super::Transport;
+    use crate::Error;
+
+    /// Position of the size in an ioctl request number, which is the same on every architecture.
+    const IOC_SIZESHIFT: u32 = 16;
+
+    /// Builds the `HIDIOCSFEATURE`/`HIDIOCGFEATURE` ioctl request numbers from `linux/hidraw.h`.
+    ///
+    /// libc only builds requests for a fixed size type, so the request is built for an empty array
+    /// and the size added afterwards. The direction bits differ between architectures, so they're
+    /// left to libc.
+    const fn hidioc_feature(nr: u32, len: usize) -> u32 {
+        libc::_IOWR::<[u8; 0]>(b'H' as u32, nr) | ((len as u32) << IOC_SIZESHIFT)
+    }
+
+    const HIDIOCSFEATURE: u32 = 0x06;
+    const HIDIOCGFEATURE: u32 = 0x07;
+
+    fn io_error(error: io::Error) -> Error {
+        Error::Transport(HidError::IoError { error })
+    }
+
+    /// A transport using a Linux `/dev/hidrawN` device directly, without hidapi.
+    #[derive(Debug)]
+    pub struct Hidraw {
+        file: File,
+    }
+
+    impl Hidraw {
+        pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+            let path = path.as_ref();
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .map(|file| Self { file })
+                .map_err(|e| {
+                    Error::from_open(HidError::IoError { error: e }, &path.display().to_string())
+                })
+        }
+
+        fn feature_ioctl(&self, nr: u32, buf: *mut u8, len: usize) -> crate::Result<usize> {
+            // SAFETY: `buf` is valid for `len` bytes, which is the size encoded in the request.
+            let res =
+                unsafe { libc::ioctl(self.file.as_raw_fd(), hidioc_feature(nr, len) as _, buf) };
+
+            if res < 0 {
+                Err(io_error(io::Error::last_os_error()))
+            } else {
+                Ok(res as usize)
+            }
+        }
+    }
+
+    impl Transport for Hidraw {
+        fn send_feature_report(&self, data: &[u8]) -> crate::Result<()> {
+            let mut data = data.to_vec();
+            self.feature_ioctl(HIDIOCSFEATURE, data.as_mut_ptr(), data.len())?;
+            Ok(())
+        }
+
+        fn get_feature_report(&self, buf: &mut [u8]) -> crate::Result<usize> {
+            self.feature_ioctl(HIDIOCGFEATURE, buf.as_mut_ptr(), buf.len())
+        }
+
+        fn read_timeout(&self, buf: &mut [u8], timeout_ms: i32) -> crate::Result<usize> {
+            let mut fd = libc::pollfd {
+                fd: self.file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            // SAFETY: `fd` is a single valid pollfd.
+            let res = unsafe { libc::poll(&mut fd, 1, timeout_ms) };
+            match res {
+                ..0 => Err(io_error(io::Error::last_os_error())),
+                0 => Ok(0),
+                _ => (&self.file).read(buf).map_err(io_error),
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::ops::RangeInclusive;
+
+use hidapi::HidError;
+
+use crate::config::ConfigError;
+use crate::device::{
+    discovery::DiscoveryError,
+    dpi::{StageColourError, StageIndexError},
+    lighting::animate::KeyframeError,
+    message::{DecodeError, MessageBuilderError},
+};
+
+/// Name of the udev rules file shipped with i2control, which allows non-root users to access
+/// supported devices.
+pub const UDEV_RULES_FILE: &str = "99-glorious-i2-wireless.rules";
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors from any operation in this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// No supported device could be found, or one couldn't be selected.
+    DeviceNotFound(DiscoveryError),
+    /// A device was found, but the current user isn't allowed to open it.
+    PermissionDenied { path: String },
+    /// Communicating with the device failed.
+    Transport(HidError),
+    /// A message couldn't be built from the given settings.
+    Encoding(MessageBuilderError),
+    /// A reply from the device couldn't be interpreted.
+    Decoding(DecodeError),
+    /// A setting was invalid.
+    Validation(ValidationError),
+    /// A config file couldn't be read.
+    Config(ConfigError),
+    /// A keyframe file for an animation couldn't be read.
+    Keyframes(KeyframeError),
+    /// An I/O operation not involving the device failed, e.g. listening for connections.
+    Io(std::io::Error),
+}
+
+impl Error {
+    /// Converts an error from opening the device at `path`, detecting permission errors.
+    pub fn from_open(error: HidError, path: &str) -> Self {
+        let denied = match &error {
+            HidError::HidApiError { message } => message.contains("Permission denied"),
+            HidError::IoError { error } => error.kind() == std::io::ErrorKind::PermissionDenied,
+            _ => false,
+        };
+
+        if denied {
+            Error::PermissionDenied {
+                path: path.to_string(),
+            }
+        } else {
+            Error::Transport(error)
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::DeviceNotFound(e) => e.fmt(f),
+            Error::PermissionDenied { path } => write!(
+                f,
+                "Permission denied opening {path}. Copy {UDEV_RULES_FILE} to /etc/udev/rules.d/ \
+                and replug the device, or run as root"
+            ),
+            Error::Transport(e) => write!(f, "Device communication failed: {e}"),
+            Error::Encoding(e) => write!(f, "Failed to build message: {e}"),
+            Error::Decoding(e) => write!(f, "Failed to read reply: {e}"),
+            Error::Validation(e) => e.fmt(f),
+            Error::Config(e) => e.fmt(f),
+            Error::Keyframes(e) => e.fmt(f),
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::DeviceNotFound(e) => Some(e),
+            Error::PermissionDenied { path: _ } => None,
+            Error::Transport(e) => Some(e),
+            Error::Encoding(e) => Some(e),
+            Error::Decoding(e) => Some(e),
+            Error::Validation(e) => Some(e),
+            Error::Config(e) => Some(e),
+            Error::Keyframes(e) => Some(e),
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<DiscoveryError> for Error {
+    fn from(e: DiscoveryError) -> Self {
+        Self::DeviceNotFound(e)
+    }
+}
+
+impl From<HidError> for Error {
+    fn from(e: HidError) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl From<MessageBuilderError> for Error {
+    fn from(e: MessageBuilderError) -> Self {
+        Self::Encoding(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Self::Decoding(e)
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        Self::Validation(e)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Self::Config(e)
+    }
+}
+
+impl From<KeyframeError> for Error {
+    fn from(e: KeyframeError) -> Self {
+        Self::Keyframes(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<StageIndexError> for Error {
+    fn from(e: StageIndexError) -> Self {
+        Self::Validation(e.into())
+    }
+}
+
+impl From<StageColourError> for Error {
+    fn from(e: StageColourError) -> Self {
+        Self::Validation(e.into())
+    }
+}
+
+/// Settings which are out of range or otherwise can't be sent to a mouse.
+#[derive(Debug)]
+pub enum ValidationError {
+    OutOfRange {
+        field: &'static str,
+        value: usize,
+        range: RangeInclusive<usize>,
+    },
+    ColourCount {
+        expected: usize,
+        found: usize,
+    },
+    StageIndex(StageIndexError),
+    StageColour(StageColourError),
+    /// Something which needs a read this build doesn't support, because its request hasn't been
+    /// captured from Glorious Core yet.
+    Unsupported(&'static str),
+}
+
+/// Checks that `value` is within `range`.
+pub(crate) fn check_range<T: Copy + PartialOrd + Into<usize>>(
+    field: &'static str,
+    value: T,
+    range: RangeInclusive<T>,
+) -> std::result::Result<(), ValidationError> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(ValidationError::OutOfRange {
+            field,
+            value: value.into(),
+            range: (*range.start()).into()..=(*range.end()).into(),
+        })
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::OutOfRange {
+                field,
+                value,
+                range,
+            } => write!(
+                f,
+                "{field} must be between {} and {} (inclusive), got {value}",
+                range.start(),
+                range.end()
+            ),
+            ValidationError::ColourCount { expected, found } => {
+                write!(f, "Expected {expected} colours, got {found}")
+            }
+            ValidationError::StageIndex(e) => e.fmt(f),
+            ValidationError::StageColour(e) => e.fmt(f),
+            ValidationError::Unsupported(name) => write!(
+                f,
+                "{name} can't be read from the mouse yet, since the request hasn't been \
+                captured from Glorious Core. Build with the unverified-reads feature to try a \
+                guessed request"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<StageIndexError> for ValidationError {
+    fn from(e: StageIndexError) -> Self {
+        Self::StageIndex(e)
+    }
+}
+
+impl From<StageColourError> for ValidationError {
+    fn from(e: StageColourError) -> Self {
+        Self::StageColour(e)
+    }
+}
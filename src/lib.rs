@@ -1,2 +1,10 @@
+pub mod cache;
+pub mod config;
 pub mod device;
+pub mod error;
+pub mod metrics;
+pub mod rpc;
+pub mod rules;
 pub mod util;
+
+pub use error::{Error, Result};
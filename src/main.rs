@@ -1,16 +1,229 @@
 mod cli;
+#[cfg(feature = "unverified-reads")]
+mod monitor;
+#[cfg(feature = "unverified-reads")]
+mod readback;
 
-use std::error::Error;
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+    thread,
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
-use hidapi::{DeviceInfo, HidApi};
+use hidapi::{DeviceInfo, HidApi, HidDevice, HidError};
+use log::{debug, error, info, warn};
+use serde::Serialize;
 
-use cli::{Cli, Commands};
-use i2control::device::{dpi::set_dpi, lighting::set_lighting, timeout::set_timeout};
+use cli::{Cli, Commands, DpiCommand, DumpFormat, OutputFormat};
+#[cfg(feature = "unverified-reads")]
+use i2control::config::get_config;
+use i2control::config::{Config, ConfigError, apply_config};
+#[cfg(feature = "unverified-reads")]
+use i2control::device::battery::{BatteryStatus, get_battery};
+use i2control::device::{
+    discovery::{
+        Candidate, DiscoveryError, KnownDevice, find_devices, known_device, select_device,
+    },
+    dpi::{DpiSettings, DpiUpdate, StageColour, StageColourError, update_dpi},
+    hotplug::{HidApiScanner, HotplugEvent, Scanner, Watcher},
+    lighting::{
+        LightingSettings,
+        animate::{self, Cycle, Effect, Gradient, Keyframes, Pulse},
+        set_lighting_settings,
+    },
+    timeout::{Timeout, set_timeout},
+    transport::Transport,
+};
+#[cfg(target_os = "linux")]
+use i2control::device::{hotplug::SysfsScanner, transport::Hidraw};
+#[cfg(not(feature = "unverified-reads"))]
+use i2control::error::ValidationError;
+use i2control::{
+    Error,
+    cache::DpiCache,
+    rules::{CpuUsage, Debouncer, Readings},
+    util::{capture::format_dump, logging, rgb::Rgb},
+};
 
-fn print_device_info(info: &DeviceInfo) {
-    println!(
-        "{}: {} ({}:{})",
+/// Settings read from or sent to the mouse.
+#[derive(Debug, Default, Serialize)]
+pub struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lighting: Option<LightingSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dpi: Option<DpiSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Timeout>,
+}
+
+impl std::fmt::Display for Settings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sections: [Option<&dyn std::fmt::Display>; 3] = [
+            self.lighting.as_ref().map(|s| s as _),
+            self.dpi.as_ref().map(|s| s as _),
+            self.timeout.as_ref().map(|s| s as _),
+        ];
+
+        for section in sections.into_iter().flatten() {
+            writeln!(f, "{section}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of a command, which is printed in the format chosen with `--format`.
+enum Output {
+    /// Settings which were sent. Only printed as JSON.
+    Sent(Settings),
+    /// Config which was applied. Only printed as JSON.
+    Applied(Config),
+    /// Settings which were read back.
+    #[cfg(feature = "unverified-reads")]
+    Settings(Settings),
+    /// Config which was exported, printed as TOML in text format.
+    #[cfg(feature = "unverified-reads")]
+    Export(Config),
+    #[cfg(feature = "unverified-reads")]
+    Battery {
+        status: BatteryStatus,
+        machine: bool,
+    },
+    Devices(Vec<Candidate>),
+    #[cfg(feature = "unverified-reads")]
+    Probe(Vec<readback::ProbeResult>),
+    /// Output was already printed while the command ran.
+    None,
+}
+
+/// Prints a value as a single line of JSON.
+fn print_json(value: &impl Serialize) -> i2control::Result<()> {
+    let json = serde_json::to_string(value).map_err(std::io::Error::from)?;
+    println!("{json}");
+    Ok(())
+}
+
+impl Output {
+    fn print(&self, format: OutputFormat) -> i2control::Result<()> {
+        match (self, format) {
+            (Output::Sent(settings), OutputFormat::Json) => print_json(settings)?,
+            (Output::Applied(config), OutputFormat::Json) => print_json(config)?,
+            #[cfg(feature = "unverified-reads")]
+            (Output::Settings(settings), OutputFormat::Json) => print_json(settings)?,
+            #[cfg(feature = "unverified-reads")]
+            (Output::Export(config), OutputFormat::Json) => print_json(config)?,
+            #[cfg(feature = "unverified-reads")]
+            (Output::Battery { status, .. }, OutputFormat::Json) => print_json(status)?,
+            (Output::Devices(candidates), OutputFormat::Json) => print_json(candidates)?,
+            #[cfg(feature = "unverified-reads")]
+            (Output::Probe(results), OutputFormat::Json) => print_json(results)?,
+            #[cfg(feature = "unverified-reads")]
+            (Output::Settings(settings), OutputFormat::Text) => print!("{settings}"),
+            #[cfg(feature = "unverified-reads")]
+            (Output::Export(config), OutputFormat::Text) => print!("{}", config.to_toml()?),
+            #[cfg(feature = "unverified-reads")]
+            (Output::Battery { status, machine }, OutputFormat::Text) => {
+                monitor::print_battery(*status, *machine)
+            }
+            (Output::Devices(candidates), OutputFormat::Text) => {
+                if candidates.is_empty() {
+                    println!("No supported devices found");
+                }
+
+                for (i, candidate) in candidates.iter().enumerate() {
+                    println!("{i}: {candidate}");
+                }
+            }
+            #[cfg(feature = "unverified-reads")]
+            (Output::Probe(results), OutputFormat::Text) => {
+                for result in results {
+                    println!("{result}");
+                }
+            }
+            (Output::Sent(_) | Output::Applied(_) | Output::None, OutputFormat::Text)
+            | (Output::None, OutputFormat::Json) => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Sets the lighting from system state whenever it changes, following the rules in a config.
+///
+/// If setting the lighting fails, it's set again at the next reading.
+fn run_rules(mouse: &(impl Transport + ?Sized), path: PathBuf) -> i2control::Result<()> {
+    let config = Config::load(path)?;
+    config.validate()?;
+    let rules = config.rules.ok_or(ConfigError::MissingSection("rules"))?;
+
+    let mut cpu = CpuUsage::new();
+    let mut debouncer = Debouncer::new(rules.debounce());
+    let start = Instant::now();
+
+    loop {
+        let readings = Readings::read(mouse, &rules, &mut cpu);
+        debug!("{readings:?}");
+
+        let change = rules
+            .evaluate(&readings)
+            .and_then(|mode| debouncer.update(mode, start.elapsed()));
+        if let Some(mode) = change {
+            info!("Setting lighting to {mode}");
+            if let Err(e) = set_lighting_settings(mouse, &rules.settings(mode)) {
+                warn!("Couldn't set lighting: {e}");
+                debouncer = Debouncer::new(rules.debounce());
+            }
+        }
+
+        thread::sleep(rules.interval());
+    }
+}
+
+/// Plays a lighting effect on the mouse.
+///
+/// Pulses are triggered by each line read from stdin, until it's closed.
+fn animate(
+    mouse: &(impl Transport + ?Sized),
+    effect: cli::Effect,
+    brightness: u8,
+    fps: f32,
+    duration: Option<Duration>,
+) -> i2control::Result<()> {
+    let mut effect: Box<dyn Effect> = match effect {
+        cli::Effect::Gradient { from, to, period } => Box::new(Gradient { from, to, period }),
+        cli::Effect::Cycle { palette, step } => Box::new(Cycle { palette, step }),
+        cli::Effect::Pulse { idle, colour, fade } => {
+            let pulse = Pulse::new(idle, colour, fade);
+            let trigger = pulse.trigger();
+            thread::spawn(move || {
+                for _ in io::stdin().lines().map_while(Result::ok) {
+                    trigger.pulse();
+                }
+            });
+            Box::new(pulse)
+        }
+        cli::Effect::Play { path, looping } => {
+            let mut keyframes = Keyframes::load(path)?;
+            keyframes.looping = looping;
+            Box::new(keyframes)
+        }
+    };
+
+    let sent = animate::play(mouse, effect.as_mut(), brightness, fps, duration)?;
+    debug!("Sent {sent} frames");
+    Ok(())
+}
+
+/// Logs which device is in use, keeping stdout for the command's output.
+fn log_device_info(info: &DeviceInfo) {
+    let mode = known_device(info.vendor_id(), info.product_id())
+        .map_or("unknown".to_string(), |d| d.mode.to_string());
+
+    info!(
+        "{}: {} ({}:{}), {mode} mode",
         info.manufacturer_string().unwrap_or("Unknown"),
         info.product_string().unwrap_or("Unknown"),
         info.vendor_id(),
@@ -18,42 +231,369 @@ fn print_device_info(info: &DeviceInfo) {
     );
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+/// Converts a colour given on the command line to a DPI stage colour, optionally snapping it to the
+/// nearest supported colour.
+fn stage_colour(col: Rgb, snap: bool) -> Result<StageColour, StageColourError> {
+    if snap {
+        Ok(StageColour::nearest(col))
+    } else {
+        StageColour::try_from(col)
+    }
+}
+
+/// Opens the connected device of the given model and applies a config to it.
+fn apply_on_connect(
+    api: &mut HidApi,
+    model: &KnownDevice,
+    config: &Config,
+    dpi_cache: Option<&DpiCache>,
+) -> i2control::Result<()> {
+    api.refresh_devices()?;
+    let candidate = find_devices(api)?
+        .into_iter()
+        .find(|c| c.model() == model)
+        .ok_or(DiscoveryError::NoDevices)?;
+    apply_config(&candidate.open(api)?, config, dpi_cache)
+}
+
+/// Prints each device being connected or disconnected, optionally applying a config to devices
+/// as they connect. Runs until scanning fails.
+///
+/// In JSON format, each event is printed as one line.
+fn watch(
+    scanner: impl Scanner,
+    config: Option<Config>,
+    interval: u64,
+    format: OutputFormat,
+) -> i2control::Result<()> {
+    let mut api = HidApi::new()?;
+    let mut watcher = Watcher::new(scanner);
+    let dpi_cache = DpiCache::from_env();
+
+    loop {
+        for event in watcher.poll()? {
+            match format {
+                OutputFormat::Text => println!("{event}"),
+                OutputFormat::Json => print_json(&event)?,
+            }
+
+            if let (HotplugEvent::Connected(model), Some(config)) = (event, &config) {
+                match apply_on_connect(&mut api, model, config, dpi_cache.as_ref()) {
+                    Ok(()) => info!("Applied config to {}", model.name),
+                    Err(e) => error!("Couldn't apply config: {e}"),
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(interval));
+    }
+}
+
+fn run_watch(
+    apply: Option<PathBuf>,
+    interval: u64,
+    hidapi: bool,
+    format: OutputFormat,
+) -> i2control::Result<()> {
+    // Validate the config up front, rather than on every connection
+    let config = apply.map(Config::load).transpose()?;
+    if let Some(config) = &config {
+        config.validate()?;
+    }
+
+    #[cfg(target_os = "linux")]
+    if !hidapi {
+        return watch(SysfsScanner::new(), config, interval, format);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = hidapi; // Only hidapi is available
+
+    watch(HidApiScanner::new(HidApi::new()?), config, interval, format)
+}
+
+/// Opens the device given by the CLI arguments, or automatically detects one.
+fn open_device(api: &HidApi, args: &Cli) -> i2control::Result<HidDevice> {
+    if let (Some(vid), Some(pid)) = (args.vid, args.pid) {
+        return api
+            .open(vid, pid)
+            .map_err(|e| Error::from_open(e, &format!("{vid:04X}:{pid:04X}")));
+    }
+
+    let candidate = select_device(find_devices(api)?, args.device)?;
+    candidate.open(api)
+}
+
+/// A transport printing each report as it's sent, instead of sending it to a device.
+///
+/// There's no device to reply, so reads fail.
+///
+/// In JSON format, each report is printed as one line, as a string under `report`.
+struct DryRun {
+    dump_format: DumpFormat,
+    format: OutputFormat,
+}
+
+impl Transport for DryRun {
+    fn send_feature_report(&self, data: &[u8]) -> i2control::Result<()> {
+        #[derive(Serialize)]
+        struct Sent {
+            report: String,
+        }
+
+        let report = match self.dump_format {
+            DumpFormat::Hex => {
+                let bytes: Vec<_> = data.iter().map(|b| format!("{b:02X}")).collect();
+                bytes.join(" ")
+            }
+            DumpFormat::Capture => format_dump(data),
+        };
+
+        // Written rather than printed, so the command stops if stdout is closed
+        let mut stdout = io::stdout().lock();
+        match self.format {
+            OutputFormat::Text => writeln!(stdout, "{report}")?,
+            OutputFormat::Json => {
+                let json = serde_json::to_string(&Sent { report }).map_err(io::Error::from)?;
+                writeln!(stdout, "{json}")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_feature_report(&self, _buf: &mut [u8]) -> i2control::Result<usize> {
+        Err(HidError::HidApiError {
+            message: "Nothing can be read in a dry run".to_string(),
+        }
+        .into())
+    }
+
+    fn read_timeout(&self, _buf: &mut [u8], _timeout_ms: i32) -> i2control::Result<usize> {
+        Ok(0)
+    }
+
+    fn report_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Prints the reports a command would send as they're sent, without opening a device.
+///
+/// Commands which read from the mouse get no replies, so they fail after printing their requests.
+/// Cached DPI settings are used, but not updated.
+fn dry_run(
+    command: Commands,
+    dump_format: DumpFormat,
+    format: OutputFormat,
+) -> i2control::Result<()> {
+    let mouse = DryRun {
+        dump_format,
+        format,
+    };
+    let dpi_cache = DpiCache::from_env().map(DpiCache::read_only);
+    run_command(&mouse, None, dpi_cache.as_ref(), command, format).map(|_| ())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> i2control::Result<()> {
     let args = Cli::parse();
-    let api = HidApi::new()?;
+    logging::init(args.log_level());
+    let format = args.format;
+
+    if let Commands::List = args.command {
+        return Output::Devices(find_devices(&HidApi::new()?)?).print(format);
+    }
+
+    if let Commands::Watch {
+        apply,
+        interval,
+        hidapi,
+    } = args.command
+    {
+        return run_watch(apply, interval, hidapi, format);
+    }
 
-    dbg!(&args);
+    #[cfg(not(feature = "unverified-reads"))]
+    if let Commands::Battery { .. } = args.command {
+        return Err(ValidationError::Unsupported("The battery").into());
+    }
+
+    if let Some(dump_format) = args.dry_run {
+        return dry_run(args.command, dump_format, format);
+    }
+
+    let dpi_cache = DpiCache::from_env();
 
-    let mouse = api.open(args.vid, args.pid)?;
+    #[cfg(target_os = "linux")]
+    if let Some(path) = &args.hidraw {
+        let mouse = Hidraw::open(path)?;
+        return run_command(&mouse, None, dpi_cache.as_ref(), args.command, format)?.print(format);
+    }
+
+    let api = HidApi::new()?;
+    let mouse = open_device(&api, &args)?;
     let info = mouse.get_device_info()?;
-    print_device_info(&info);
+    log_device_info(&info);
 
-    match args.command {
+    let model = known_device(info.vendor_id(), info.product_id());
+    run_command(&mouse, model, dpi_cache.as_ref(), args.command, format)?.print(format)
+}
+
+/// Runs a command which uses the mouse. `model` is used for display only, and is `None` if it
+/// isn't known. DPI settings which are sent are stored in `dpi_cache`.
+///
+/// Commands which keep running print their own output in the given format.
+#[cfg_attr(not(feature = "unverified-reads"), allow(unused_variables))] // Only used by monitoring
+fn run_command(
+    mouse: &(impl Transport + ?Sized),
+    model: Option<&'static KnownDevice>,
+    dpi_cache: Option<&DpiCache>,
+    command: Commands,
+    format: OutputFormat,
+) -> i2control::Result<Output> {
+    let output = match command {
         Commands::Lighting {
             brightness,
             rate,
+            wireless_brightness,
+            wireless_rate,
+            colours,
             mode,
-        } => set_lighting(&mouse, brightness, rate, mode)?,
+        } => {
+            let mut settings = LightingSettings::new(mode, brightness, rate).with_wireless(
+                wireless_brightness.unwrap_or(brightness),
+                wireless_rate.unwrap_or(rate),
+            );
+            if !colours.is_empty() {
+                settings = settings.with_colours(colours);
+            }
+
+            set_lighting_settings(mouse, &settings)?;
+            Output::Sent(Settings {
+                lighting: Some(settings),
+                ..Default::default()
+            })
+        }
+        Commands::Dpi {
+            command:
+                Some(DpiCommand::Stage {
+                    index,
+                    dpi,
+                    activate,
+                    colour,
+                    snap,
+                }),
+            ..
+        } => {
+            let update = DpiUpdate {
+                active_stage: activate.then_some(index),
+                stage_dpi: dpi.map(|dpi| (index, dpi)),
+                stage_colour: colour
+                    .map(|c| stage_colour(c, snap).map(|c| (index, c)))
+                    .transpose()?,
+                ..Default::default()
+            };
+            Output::Sent(Settings {
+                dpi: Some(update_dpi(mouse, &update, dpi_cache)?),
+                ..Default::default()
+            })
+        }
         Commands::Dpi {
+            active_stage,
             lift_off_distance,
             debounce_time,
             polling_rate,
             dpi_stages,
+            colours,
+            snap,
+            command: None,
         } => {
-            set_dpi(
-                &mouse,
+            let colours = colours
+                .into_iter()
+                .map(|c| stage_colour(c, snap))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let update = DpiUpdate {
+                active_stage,
+                stages: (!dpi_stages.is_empty()).then_some(dpi_stages),
+                colours: (!colours.is_empty()).then_some(colours),
+                stage_dpi: None,
+                stage_colour: None,
                 lift_off_distance,
                 debounce_time,
                 polling_rate,
-                dpi_stages,
-            )?;
+            };
+            Output::Sent(Settings {
+                dpi: Some(update_dpi(mouse, &update, dpi_cache)?),
+                ..Default::default()
+            })
         }
         Commands::Timeout {
             disable: _,
             minutes,
-        } => set_timeout(&mouse, minutes)?,
-        _ => todo!(),
-    }
+        } => {
+            let timeout = Timeout::from(minutes);
+            set_timeout(mouse, timeout)?;
+            Output::Sent(Settings {
+                timeout: Some(timeout),
+                ..Default::default()
+            })
+        }
+        Commands::Apply { path } => {
+            let config = Config::load(path)?;
+            apply_config(mouse, &config, dpi_cache)?;
+            Output::Applied(config)
+        }
+        Commands::Rules { path } => {
+            run_rules(mouse, path)?;
+            Output::None
+        }
+        #[cfg(feature = "unverified-reads")]
+        Commands::Export => Output::Export(get_config(mouse)?),
+        #[cfg(feature = "unverified-reads")]
+        Commands::Get { setting } => Output::Settings(readback::read_settings(mouse, setting)?),
+        #[cfg(feature = "unverified-reads")]
+        Commands::Battery { machine, watch } if watch.enabled => {
+            monitor::watch_battery(mouse, &watch, machine, format)?;
+            Output::None
+        }
+        #[cfg(feature = "unverified-reads")]
+        Commands::Battery { machine, watch: _ } => Output::Battery {
+            status: get_battery(mouse)?,
+            machine,
+        },
+        Commands::Animate {
+            fps,
+            brightness,
+            duration,
+            effect,
+        } => {
+            animate(mouse, effect, brightness, fps, duration)?;
+            Output::None
+        }
+        #[cfg(feature = "unverified-reads")]
+        Commands::ProbePalette { colour, mode } => {
+            Output::Probe(readback::probe_lighting(mouse, mode, colour)?)
+        }
+        #[cfg(feature = "unverified-reads")]
+        Commands::ServeMetrics { listen } => {
+            monitor::serve_metrics(mouse, model, listen)?;
+            Output::None
+        }
+        #[cfg(not(feature = "unverified-reads"))]
+        Commands::Battery { .. } => unreachable!(),
+        Commands::List | Commands::Watch { .. } => unreachable!(),
+    };
 
-    Ok(())
+    Ok(output)
 }
@@ -0,0 +1,123 @@
+#[cfg(feature = "unverified-reads")]
+use log::warn;
+
+use crate::device::{
+    battery::BatteryStatus,
+    discovery::{ConnectionMode, KnownDevice},
+    dpi::DpiSettings,
+};
+#[cfg(feature = "unverified-reads")]
+use crate::device::{battery::get_battery, dpi::get_dpi, transport::Transport};
+
+/// Content type of [`Metrics`] when served over HTTP.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Device state which can be exported as OpenMetrics text. Example output:
+/// ```text
+/// # TYPE i2control_up gauge
+/// # HELP i2control_up Whether every reading from the mouse succeeded.
+/// i2control_up 1
+/// # TYPE i2control_battery_percent gauge
+/// # HELP i2control_battery_percent Battery level.
+/// i2control_battery_percent 85
+/// ...
+/// # EOF
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub model: Option<&'static KnownDevice>,
+    pub battery: Option<BatteryStatus>,
+    pub dpi: Option<DpiSettings>,
+}
+
+impl Metrics {
+    /// Reads every metric from the mouse, using the same readback as the CLI.
+    ///
+    /// Readings which fail are logged and left out, which is shown by `i2control_up`.
+    #[cfg(feature = "unverified-reads")]
+    pub fn read(mouse: &(impl Transport + ?Sized), model: Option<&'static KnownDevice>) -> Self {
+        Self {
+            model,
+            battery: get_battery(mouse)
+                .inspect_err(|e| warn!("Couldn't read battery: {e}"))
+                .ok(),
+            dpi: get_dpi(mouse)
+                .inspect_err(|e| warn!("Couldn't read DPI settings: {e}"))
+                .ok(),
+        }
+    }
+}
+
+/// Writes a single metric family with one unlabelled sample.
+fn gauge(
+    f: &mut std::fmt::Formatter<'_>,
+    name: &str,
+    help: &str,
+    value: impl std::fmt::Display,
+) -> std::fmt::Result {
+    writeln!(f, "# TYPE {name} gauge")?;
+    writeln!(f, "# HELP {name} {help}")?;
+    writeln!(f, "{name} {value}")
+}
+
+impl std::fmt::Display for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let up = self.battery.is_some() && self.dpi.is_some();
+        gauge(
+            f,
+            "i2control_up",
+            "Whether every reading from the mouse succeeded.",
+            up as u8,
+        )?;
+
+        if let Some(model) = self.model {
+            let mode = match model.mode {
+                ConnectionMode::Wired => "wired",
+                ConnectionMode::Wireless => "wireless",
+            };
+
+            writeln!(f, "# TYPE i2control_device info")?;
+            writeln!(f, "# HELP i2control_device Model and connection mode.")?;
+            writeln!(
+                f,
+                "i2control_device_info{{model=\"{}\",mode=\"{mode}\"}} 1",
+                model.name
+            )?;
+        }
+
+        if let Some(battery) = self.battery {
+            gauge(
+                f,
+                "i2control_battery_percent",
+                "Battery level.",
+                battery.percentage,
+            )?;
+            gauge(
+                f,
+                "i2control_battery_charging",
+                "Whether the battery is charging.",
+                battery.charging as u8,
+            )?;
+        }
+
+        if let Some(dpi) = &self.dpi {
+            if let Some(stage) = dpi.stages.get(dpi.active_stage as usize) {
+                gauge(f, "i2control_dpi", "DPI of the active stage.", stage.dpi)?;
+            }
+
+            gauge(
+                f,
+                "i2control_dpi_stage",
+                "Index of the active DPI stage.",
+                dpi.active_stage,
+            )?;
+
+            writeln!(f, "# TYPE i2control_polling_rate_hertz gauge")?;
+            writeln!(f, "# UNIT i2control_polling_rate_hertz hertz")?;
+            writeln!(f, "# HELP i2control_polling_rate_hertz Polling rate.")?;
+            writeln!(f, "i2control_polling_rate_hertz {}", dpi.polling_rate)?;
+        }
+
+        writeln!(f, "# EOF")
+    }
+}
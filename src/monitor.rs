@@ -0,0 +1,198 @@
+//! Commands monitoring the mouse, which read the battery using a request that hasn't been
+//! captured from Glorious Core yet.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+use log::{info, warn};
+use serde::Serialize;
+
+use i2control::{
+    device::{
+        battery::{BatteryEvent, BatteryMonitor, BatteryStatus, get_battery},
+        discovery::KnownDevice,
+        transport::Transport,
+    },
+    metrics::{self, Metrics},
+};
+
+use crate::{
+    cli::{BatteryWatch, OutputFormat},
+    print_json,
+};
+
+pub fn print_battery(status: BatteryStatus, machine: bool) {
+    if machine {
+        println!("{} {}", status.percentage, status.charging_state());
+    } else {
+        println!("Battery: {status}");
+    }
+}
+
+/// Runs the user's hook command for a battery event.
+fn run_battery_hook(hook: &str, event: BatteryEvent, status: BatteryStatus) {
+    let result = Command::new("sh")
+        .arg("-c")
+        .arg(hook)
+        .env("I2CONTROL_EVENT", event.name())
+        .env("I2CONTROL_BATTERY", status.percentage.to_string())
+        .env("I2CONTROL_CHARGING", status.charging_state())
+        .status();
+
+    match result {
+        Ok(exit) if !exit.success() => warn!("Battery hook failed ({exit})"),
+        Ok(_) => (),
+        Err(e) => warn!("Couldn't run battery hook: {e}"),
+    }
+}
+
+/// Sends a desktop notification for a battery event through notify-send, which uses the
+/// freedesktop notification service on the session D-Bus.
+fn notify_battery(event: BatteryEvent, status: BatteryStatus) {
+    let urgency = match event {
+        BatteryEvent::Low { threshold: _ } => "critical",
+        BatteryEvent::ChargingStarted | BatteryEvent::ChargingStopped => "normal",
+    };
+
+    let result = Command::new("notify-send")
+        .args([
+            "--app-name=i2control",
+            "--urgency",
+            urgency,
+            "Mouse battery",
+        ])
+        .arg(format!("{event} ({status})"))
+        .status();
+
+    if let Err(e) = result {
+        warn!("Couldn't send notification: {e}");
+    }
+}
+
+/// Reads the battery until interrupted, printing each change and reporting warnings and changes
+/// in charging state.
+///
+/// Failed readings are reported and skipped, since the mouse may be asleep.
+///
+/// In JSON format, each changed reading is printed as one line, along with its events.
+pub fn watch_battery(
+    mouse: &(impl Transport + ?Sized),
+    args: &BatteryWatch,
+    machine: bool,
+    format: OutputFormat,
+) -> i2control::Result<()> {
+    #[derive(Serialize)]
+    struct Reading {
+        #[serde(flatten)]
+        status: BatteryStatus,
+        events: Vec<BatteryEvent>,
+    }
+
+    let mut monitor = BatteryMonitor::new(args.thresholds.clone());
+
+    loop {
+        match get_battery(mouse) {
+            Ok(status) => {
+                let changed = monitor.last() != Some(status);
+                let events = monitor.update(status);
+
+                match format {
+                    OutputFormat::Json if changed || !events.is_empty() => print_json(&Reading {
+                        status,
+                        events: events.clone(),
+                    })?,
+                    OutputFormat::Json => (),
+                    OutputFormat::Text => {
+                        if changed {
+                            print_battery(status, machine);
+                        }
+
+                        if !machine {
+                            events.iter().for_each(|event| println!("{event}"));
+                        }
+                    }
+                }
+
+                for event in events {
+                    if let Some(hook) = &args.hook {
+                        run_battery_hook(hook, event, status);
+                    }
+
+                    if args.notify {
+                        notify_battery(event, status);
+                    }
+                }
+            }
+            Err(e) => warn!("Couldn't read battery: {e}"),
+        }
+
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
+
+/// Time to wait for a client to send its request or read the response.
+///
+/// Requests are answered one at a time, so a client which stalls would otherwise block every other
+/// client.
+const METRICS_CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Answers a single HTTP request, serving metrics at `/metrics`.
+fn answer_metrics_request(
+    stream: TcpStream,
+    mouse: &(impl Transport + ?Sized),
+    model: Option<&'static KnownDevice>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(METRICS_CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(METRICS_CLIENT_TIMEOUT))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Skip the headers, up to the blank line before the body
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            metrics::CONTENT_TYPE,
+            Metrics::read(mouse, model).to_string(),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+
+    write!(
+        &stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+        Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Serves metrics over HTTP until interrupted. Requests are answered one at a time.
+pub fn serve_metrics(
+    mouse: &(impl Transport + ?Sized),
+    model: Option<&'static KnownDevice>,
+    listen: SocketAddr,
+) -> i2control::Result<()> {
+    let listener = TcpListener::bind(listen)?;
+    info!("Serving metrics at http://{listen}/metrics");
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| answer_metrics_request(stream, mouse, model));
+        if let Err(e) = result {
+            warn!("Couldn't answer metrics request: {e}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! Commands reading settings back from the mouse, using requests that haven't been captured from
+//! Glorious Core yet.
+
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use i2control::{
+    device::{
+        dpi::get_dpi,
+        lighting::{self, LightingSettings, get_lighting, probe_palette, set_lighting_settings},
+        transport::Transport,
+    },
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+use crate::{Settings, cli::Setting};
+
+/// Reads the given settings from the mouse.
+pub fn read_settings(
+    mouse: &(impl Transport + ?Sized),
+    setting: Setting,
+) -> i2control::Result<Settings> {
+    let mut settings = Settings::default();
+
+    if matches!(setting, Setting::All | Setting::Lighting) {
+        settings.lighting = Some(get_lighting(mouse)?);
+    }
+
+    if matches!(setting, Setting::All | Setting::Dpi) {
+        settings.dpi = Some(get_dpi(mouse)?);
+    }
+
+    Ok(settings)
+}
+
+/// Whether the firmware used one position of a lighting mode's palette.
+#[derive(Debug, Serialize)]
+pub struct ProbeResult {
+    position: usize,
+    /// Whether the colour was read back from the mouse.
+    stored: bool,
+    /// Whether the colour was shown, as answered by the user.
+    shown: bool,
+}
+
+impl std::fmt::Display for ProbeResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        write!(
+            f,
+            "Position {}: stored {}, shown {}",
+            self.position,
+            yes_no(self.stored),
+            yes_no(self.shown)
+        )
+    }
+}
+
+/// Asks a yes/no question on stderr, keeping stdout for the command's output.
+fn confirm(question: &str) -> io::Result<bool> {
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Sends a palette lighting up each position of the mode in turn, asking whether it's shown.
+///
+/// Probes use full brightness, and the original lighting is restored afterwards, even if asking
+/// fails.
+pub fn probe_lighting(
+    mouse: &(impl Transport + ?Sized),
+    mode: LightingMode,
+    colour: Rgb,
+) -> i2control::Result<Vec<ProbeResult>> {
+    let original = get_lighting(mouse)?;
+    let brightness = *lighting::BRIGHTNESS_RANGE.end();
+    let base = LightingSettings::new(mode, brightness, original.rate)
+        .with_wireless(brightness, original.wireless_rate);
+
+    let probe = |position| -> i2control::Result<ProbeResult> {
+        let settings = base
+            .clone()
+            .with_colours(probe_palette(mode, position, colour));
+        set_lighting_settings(mouse, &settings)?;
+        let stored = get_lighting(mouse)?.colours == settings.colours;
+
+        let shown = confirm(&format!(
+            "Position {position}: is the mouse showing {colour}, and otherwise black?"
+        ))?;
+        Ok(ProbeResult {
+            position,
+            stored,
+            shown,
+        })
+    };
+
+    let results = (0..mode.num_colours() as usize).map(probe).collect();
+    set_lighting_settings(mouse, &original)?;
+    results
+}
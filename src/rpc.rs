@@ -0,0 +1,200 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use log::warn;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    Error,
+    cache::DpiCache,
+    config::{Config, apply_config},
+    device::transport::Transport,
+};
+#[cfg(feature = "unverified-reads")]
+use crate::{config::get_config, device::battery::get_battery};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Errors from the mouse itself, e.g. when it isn't connected.
+pub const DEVICE_ERROR: i64 = -32000;
+
+/// A connection to the mouse which is opened when it's first needed, and reopened after it's lost.
+///
+/// `connect` finds and opens the mouse, e.g. with
+/// [`select_device`](crate::device::discovery::select_device).
+pub struct Device<T, F> {
+    connect: F,
+    mouse: Option<T>,
+    dpi_cache: Option<DpiCache>,
+}
+
+impl<T: Transport, F: FnMut() -> crate::Result<T>> Device<T, F> {
+    pub fn new(connect: F) -> Self {
+        Self {
+            connect,
+            mouse: None,
+            dpi_cache: None,
+        }
+    }
+
+    /// Stores the DPI settings sent by each request in `cache`, and keeps the cached settings which
+    /// a request doesn't change.
+    pub fn with_dpi_cache(self, cache: Option<DpiCache>) -> Self {
+        Self {
+            dpi_cache: cache,
+            ..self
+        }
+    }
+
+    /// Returns the open mouse, opening it first if needed.
+    pub fn connect(&mut self) -> crate::Result<&T> {
+        if self.mouse.is_none() {
+            self.mouse = Some((self.connect)()?);
+        }
+
+        Ok(self.mouse.as_ref().unwrap())
+    }
+
+    /// Runs `f` with the mouse.
+    ///
+    /// If communicating with the mouse fails, it may have been unplugged, so it's reopened and `f`
+    /// is retried once.
+    pub fn with_mouse<R>(&mut self, f: impl Fn(&T) -> crate::Result<R>) -> crate::Result<R> {
+        let result = f(self.connect()?);
+        if let Err(Error::Transport(e)) = &result {
+            warn!("Lost connection to the mouse ({e}), reconnecting");
+            self.mouse = None;
+            return f(self.connect()?);
+        }
+
+        result
+    }
+}
+
+/// Locks the device for a request.
+///
+/// A request which panicked may have left the lock poisoned, but the device is still usable, so
+/// later requests are still served.
+fn lock<T, F>(device: &Mutex<Device<T, F>>) -> MutexGuard<'_, Device<T, F>> {
+    device.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A JSON-RPC 2.0 request.
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Requests without an ID are notifications, which get no response.
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Validation(_) => Self::new(INVALID_PARAMS, e),
+            _ => Self::new(DEVICE_ERROR, e),
+        }
+    }
+}
+
+#[cfg(feature = "unverified-reads")]
+fn to_value(value: impl serde::Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(DEVICE_ERROR, e))
+}
+
+/// Runs a request with the mouse, returning its result.
+///
+/// Supported methods are `apply`, which takes a [`Config`] as params, and with the
+/// `unverified-reads` feature `get` and `battery`.
+pub fn handle<T: Transport, F: FnMut() -> crate::Result<T>>(
+    device: &Mutex<Device<T, F>>,
+    request: Request,
+) -> Result<Value, RpcError> {
+    if request.jsonrpc != "2.0" {
+        return Err(RpcError::new(
+            INVALID_REQUEST,
+            "Only JSON-RPC 2.0 is supported",
+        ));
+    }
+
+    match request.method.as_str() {
+        #[cfg(feature = "unverified-reads")]
+        "get" => to_value(lock(device).with_mouse(get_config)?),
+        "apply" => {
+            let config: Config = serde_json::from_value(request.params)
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            config.validate().map_err(Error::from)?;
+
+            let mut device = lock(device);
+            let cache = device.dpi_cache.clone();
+            device.with_mouse(|mouse| apply_config(mouse, &config, cache.as_ref()))?;
+            Ok(Value::Null)
+        }
+        #[cfg(feature = "unverified-reads")]
+        "battery" => to_value(lock(device).with_mouse(get_battery)?),
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {method}"),
+        )),
+    }
+}
+
+/// Builds the response to the request with the given ID.
+pub fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(RpcError { code, message }) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    }
+}
+
+/// Parses and handles a line sent by a client, returning the response to send back.
+///
+/// Notifications are still handled, but get no response.
+pub fn respond<T: Transport, F: FnMut() -> crate::Result<T>>(
+    device: &Mutex<Device<T, F>>,
+    line: &str,
+) -> Option<Value> {
+    let request = match serde_json::from_str::<Value>(line) {
+        Err(e) => return Some(response(Value::Null, Err(RpcError::new(PARSE_ERROR, e)))),
+        Ok(request) => match serde_json::from_value::<Request>(request) {
+            Err(e) => {
+                return Some(response(
+                    Value::Null,
+                    Err(RpcError::new(INVALID_REQUEST, e)),
+                ));
+            }
+            Ok(request) => request,
+        },
+    };
+
+    match request.id.clone() {
+        Some(id) => Some(response(id, handle(device, request))),
+        None => {
+            let _ = handle(device, request);
+            None
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "unverified-reads")]
+use crate::device::{battery::get_battery, dpi::get_dpi};
+use crate::{
+    device::{
+        lighting::{BRIGHTNESS_RANGE, LightingSettings, RATE_RANGE},
+        transport::Transport,
+    },
+    error::{ValidationError, check_range},
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+/// Brightness used when the rules don't give one.
+const DEFAULT_BRIGHTNESS: u8 = 20;
+
+/// Breathing rate used when the rules don't give one.
+const DEFAULT_RATE: u8 = 10;
+
+/// Seconds between readings when the rules don't give an interval.
+const DEFAULT_INTERVAL: u16 = 5;
+
+/// Seconds a change must last before it's sent, when the rules don't give a debounce time.
+const DEFAULT_DEBOUNCE: u16 = 10;
+
+/// System state which rules can react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Signal {
+    /// Battery percentage of the mouse.
+    Battery,
+    /// Percentage of CPU time spent busy since the previous reading. Only available on Linux.
+    Cpu,
+    /// Index of the active DPI stage.
+    DpiStage,
+}
+
+impl Signal {
+    /// Returns what would be read from the mouse for the signal, if this build can't read it.
+    ///
+    /// Requests reading from the mouse haven't been captured from Glorious Core yet, so they're
+    /// only available with the `unverified-reads` feature.
+    fn unsupported_read(self) -> Option<&'static str> {
+        match self {
+            _ if cfg!(feature = "unverified-reads") => None,
+            Signal::Battery => Some("The battery"),
+            Signal::DpiStage => Some("The active DPI stage"),
+            Signal::Cpu => None,
+        }
+    }
+}
+
+/// How the colour of a matching rule is shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuleEffect {
+    #[default]
+    Solid,
+    Breathing,
+}
+
+/// A colour to show while a signal meets every given condition.
+///
+/// A rule without conditions always matches, so can be used as a fallback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub signal: Signal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub above: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub below: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equals: Option<u8>,
+    #[serde(alias = "color")]
+    pub colour: Rgb,
+    #[serde(default)]
+    pub effect: RuleEffect,
+}
+
+impl Rule {
+    /// Checks whether the rule's signal was read and meets every condition.
+    pub fn matches(&self, readings: &Readings) -> bool {
+        let Some(value) = readings.get(self.signal) else {
+            return false;
+        };
+
+        self.above.is_none_or(|above| value > above.into())
+            && self.below.is_none_or(|below| value < below.into())
+            && self.equals.is_none_or(|equals| value == f32::from(equals))
+    }
+
+    /// Returns the lighting mode showing the rule's colour.
+    pub fn mode(&self) -> LightingMode {
+        let col = self.colour;
+        match self.effect {
+            RuleEffect::Solid => LightingMode::SingleColour { col },
+            RuleEffect::Breathing => LightingMode::BreathingSingleColour { col },
+        }
+    }
+}
+
+/// Rules section of a [`Config`](crate::config::Config), which maps system state to lighting.
+///
+/// Rules are checked in order, and the first one which matches sets the lighting. If none match,
+/// the lighting is left as it is. Rules are only run by `i2control rules`, and are ignored when
+/// applying a config. The `battery` and `dpi-stage` signals need the `unverified-reads` feature.
+/// Example:
+/// ```
+/// # use i2control::config::Config;
+/// let config: Config = r##"
+///     [rules]
+///     interval = 5 # seconds between readings
+///     debounce = 10 # seconds a change must last before it's sent
+///
+///     [[rules.when]]
+///     signal = "cpu"
+///     above = 80
+///     colour = "red"
+///     effect = "breathing"
+///
+///     [[rules.when]]
+///     signal = "cpu"
+///     colour = "green"
+/// "##.parse().unwrap();
+/// config.validate().unwrap();
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RulesConfig {
+    /// Ranges from 0 to 20 (inclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    /// Rate of breathing rules. Ranges from 1 to 20 (inclusive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<u8>,
+    /// Seconds between readings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u16>,
+    /// Seconds a change must last before it's sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debounce: Option<u16>,
+    #[serde(default)]
+    pub when: Vec<Rule>,
+}
+
+impl RulesConfig {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        check_range("brightness", self.brightness(), BRIGHTNESS_RANGE)?;
+        check_range("rate", self.rate(), RATE_RANGE)?;
+        check_range(
+            "interval",
+            self.interval.unwrap_or(DEFAULT_INTERVAL),
+            1..=u16::MAX,
+        )?;
+
+        let unsupported = self
+            .when
+            .iter()
+            .find_map(|rule| rule.signal.unsupported_read());
+        if let Some(read) = unsupported {
+            return Err(ValidationError::Unsupported(read));
+        }
+
+        Ok(())
+    }
+
+    fn brightness(&self) -> u8 {
+        self.brightness.unwrap_or(DEFAULT_BRIGHTNESS)
+    }
+
+    fn rate(&self) -> u8 {
+        self.rate.unwrap_or(DEFAULT_RATE)
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(DEFAULT_INTERVAL).into())
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_secs(self.debounce.unwrap_or(DEFAULT_DEBOUNCE).into())
+    }
+
+    /// Checks whether any rule reacts to the given signal, so that it needs to be read.
+    pub fn uses(&self, signal: Signal) -> bool {
+        self.when.iter().any(|rule| rule.signal == signal)
+    }
+
+    /// Returns the lighting mode of the first rule which matches, if any.
+    pub fn evaluate(&self, readings: &Readings) -> Option<LightingMode> {
+        self.when
+            .iter()
+            .find(|rule| rule.matches(readings))
+            .map(Rule::mode)
+    }
+
+    /// Returns the settings showing the given lighting mode.
+    pub fn settings(&self, mode: LightingMode) -> LightingSettings {
+        LightingSettings::new(mode, self.brightness(), self.rate())
+    }
+}
+
+/// Values of each signal, which are `None` if they weren't read.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Readings {
+    pub battery: Option<f32>,
+    pub cpu: Option<f32>,
+    pub dpi_stage: Option<f32>,
+}
+
+impl Readings {
+    /// Reads the signals used by the rules.
+    ///
+    /// Readings which fail are left out, so rules using them don't match.
+    pub fn read(
+        mouse: &(impl Transport + ?Sized),
+        rules: &RulesConfig,
+        cpu: &mut CpuUsage,
+    ) -> Self {
+        let mut readings = Self {
+            cpu: rules.uses(Signal::Cpu).then(|| cpu.sample()).flatten(),
+            ..Default::default()
+        };
+
+        readings.read_mouse(mouse, rules);
+        readings
+    }
+
+    /// Reads the signals which come from the mouse. Their requests are guesses, so they're only
+    /// read with the `unverified-reads` feature.
+    #[cfg(feature = "unverified-reads")]
+    fn read_mouse(&mut self, mouse: &(impl Transport + ?Sized), rules: &RulesConfig) {
+        if rules.uses(Signal::Battery) {
+            self.battery = get_battery(mouse).ok().map(|b| b.percentage.into());
+        }
+
+        if rules.uses(Signal::DpiStage) {
+            self.dpi_stage = get_dpi(mouse).ok().map(|d| d.active_stage.into());
+        }
+    }
+
+    #[cfg(not(feature = "unverified-reads"))]
+    fn read_mouse(&mut self, _mouse: &(impl Transport + ?Sized), _rules: &RulesConfig) {}
+
+    pub fn get(&self, signal: Signal) -> Option<f32> {
+        match signal {
+            Signal::Battery => self.battery,
+            Signal::Cpu => self.cpu,
+            Signal::DpiStage => self.dpi_stage,
+        }
+    }
+}
+
+/// Holds back changes until they've lasted for a delay, so that a signal hovering around a
+/// threshold doesn't send a message on every reading.
+#[derive(Debug)]
+pub struct Debouncer<T> {
+    delay: Duration,
+    sent: Option<T>,
+    pending: Option<(T, Duration)>,
+}
+
+impl<T: Clone + PartialEq> Debouncer<T> {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            sent: None,
+            pending: None,
+        }
+    }
+
+    /// Returns the value to send, given the value wanted at time `now`.
+    ///
+    /// The first value is sent immediately. After that, a value is only sent once it has been
+    /// wanted for the whole delay, and differs from the last value sent.
+    pub fn update(&mut self, value: T, now: Duration) -> Option<T> {
+        if self.sent.as_ref() == Some(&value) {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match &self.pending {
+            Some((pending, since)) if *pending == value => *since,
+            _ => {
+                self.pending = Some((value.clone(), now));
+                now
+            }
+        };
+
+        if self.sent.is_none() || now.saturating_sub(since) >= self.delay {
+            self.pending = None;
+            self.sent = Some(value.clone());
+            return Some(value);
+        }
+
+        None
+    }
+}
+
+/// Measures CPU usage between samples.
+#[derive(Debug)]
+pub struct CpuUsage {
+    last: Option<CpuTimes>,
+}
+
+/// Total and idle CPU time, in the units of `/proc/stat`.
+#[derive(Debug, Clone, Copy)]
+struct CpuTimes {
+    total: u64,
+    idle: u64,
+}
+
+/// Reads the CPU times of all CPUs from the first line of `/proc/stat`.
+#[cfg(target_os = "linux")]
+fn cpu_times() -> Option<CpuTimes> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let times: Vec<u64> = stat
+        .lines()
+        .next()?
+        .strip_prefix("cpu ")?
+        .split_whitespace()
+        .map(|t| t.parse().ok())
+        .collect::<Option<_>>()?;
+
+    // user, nice, system, idle, iowait, irq, softirq and steal. Guest time is part of user time.
+    let total = times.iter().take(8).sum();
+    let idle = times.get(3)? + times.get(4).unwrap_or(&0);
+    Some(CpuTimes { total, idle })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_times() -> Option<CpuTimes> {
+    None
+}
+
+impl CpuUsage {
+    /// Starts measuring, so that the first sample covers the time since this is created.
+    pub fn new() -> Self {
+        Self { last: cpu_times() }
+    }
+
+    /// Returns the percentage of CPU time spent busy since the previous sample, or `None` if it
+    /// can't be measured.
+    pub fn sample(&mut self) -> Option<f32> {
+        let times = cpu_times()?;
+        let last = self.last.replace(times)?;
+
+        let total = times.total.checked_sub(last.total).filter(|t| *t > 0)?;
+        let idle = times.idle.saturating_sub(last.idle);
+        Some(100.0 * (1.0 - idle as f32 / total as f32))
+    }
+}
+
+impl Default for CpuUsage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,108 @@
+use std::{fs, io, num::ParseIntError, path::Path};
+
+/// Number of bytes in each captured report. Glorious Core pads reports to this length with zeros.
+pub const CAPTURE_REPORT_LEN: usize = 64;
+
+/// Number of bytes on each line of a dump.
+const DUMP_LINE_LEN: usize = 16;
+
+#[derive(Debug)]
+pub enum ParseDumpError {
+    OffsetError { line: usize },
+    ParseIntError { line: usize, error: ParseIntError },
+}
+
+impl std::fmt::Display for ParseDumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseDumpError::OffsetError { line } => {
+                write!(f, "Line {line} doesn't start at the expected offset")
+            }
+            ParseDumpError::ParseIntError { line, error } => {
+                write!(f, "Invalid byte on line {line}: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseDumpError {}
+
+/// Parses a hex dump of a single report, in the format exported by Wireshark.
+///
+/// Each line begins with a hex offset, followed by the bytes at that offset as space-separated hex
+/// pairs. Example:
+/// ```
+/// # use i2control::util::capture::parse_dump;
+/// let bytes = parse_dump("0000   03 06 fb 05\n0004   00").unwrap();
+/// assert_eq!(bytes, [0x03, 0x06, 0xFB, 0x05, 0x00]);
+/// ```
+pub fn parse_dump(s: &str) -> Result<Vec<u8>, ParseDumpError> {
+    let mut bytes = Vec::new();
+    for (line_i, line) in s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let line_num = line_i + 1;
+        let mut words = line.split_whitespace();
+
+        let offset = words.next().unwrap_or_default();
+        let offset =
+            usize::from_str_radix(offset, 16).map_err(|error| ParseDumpError::ParseIntError {
+                line: line_num,
+                error,
+            })?;
+        if offset != bytes.len() {
+            return Err(ParseDumpError::OffsetError { line: line_num });
+        }
+
+        for word in words {
+            let byte =
+                u8::from_str_radix(word, 16).map_err(|error| ParseDumpError::ParseIntError {
+                    line: line_num,
+                    error,
+                })?;
+            bytes.push(byte);
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Formats a report as a hex dump, in the same format as [`parse_dump`] accepts.
+///
+/// The report is padded with zeros to [`CAPTURE_REPORT_LEN`] bytes, so that it matches reports
+/// captured from Glorious Core. Example:
+/// ```
+/// # use i2control::util::capture::{format_dump, parse_dump};
+/// let dump = format_dump(&[0x03, 0x06, 0xFB, 0x05]);
+/// assert!(dump.starts_with("0000   03 06 fb 05 00"));
+/// assert_eq!(parse_dump(&dump).unwrap().len(), 64);
+/// ```
+pub fn format_dump(report: &[u8]) -> String {
+    let mut bytes = report.to_vec();
+    if bytes.len() < CAPTURE_REPORT_LEN {
+        bytes.resize(CAPTURE_REPORT_LEN, 0x00);
+    }
+
+    let mut dump = String::new();
+    for (line_i, line) in bytes.chunks(DUMP_LINE_LEN).enumerate() {
+        let line: Vec<_> = line.iter().map(|b| format!("{b:02x}")).collect();
+        dump += &format!("{:04x}   {}\n", line_i * DUMP_LINE_LEN, line.join(" "));
+    }
+
+    dump
+}
+
+/// Loads a captured message from a directory containing one dump per report, named `1`, `2`, etc.
+pub fn load_capture(dir: impl AsRef<Path>) -> io::Result<Vec<Vec<u8>>> {
+    let mut reports = Vec::new();
+    for i in 1.. {
+        let path = dir.as_ref().join(i.to_string());
+        if !path.exists() {
+            break;
+        }
+
+        let report = parse_dump(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
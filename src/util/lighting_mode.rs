@@ -1,10 +1,15 @@
 use clap::Subcommand;
+use serde::{Deserialize, Serialize};
 
 use super::rgb::Rgb;
+use crate::rgb;
 
 /// Lighting effects corresponding to the options in Glorious Core.
 /// Variants with an RGB value require a specified custom colour.
-#[derive(Debug, Subcommand)]
+///
+/// Serialised with the mode name under `mode`, along with the custom colour under `colour`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "kebab-case")]
 pub enum LightingMode {
     Off,
     Glorious,
@@ -12,24 +17,73 @@ pub enum LightingMode {
     // TODO: Make help for rgb command show that these modes require a colour.
     //       It would also be nice to work out how to make these fields anonymous.
     Breathing {
+        #[serde(rename = "colour", alias = "color")]
         col: Rgb,
     },
     #[command(alias = "single-color")]
+    #[serde(alias = "single-color")]
     SingleColour {
+        #[serde(rename = "colour", alias = "color")]
         col: Rgb,
     },
     #[command(alias = "breathing-single-color")]
+    #[serde(alias = "breathing-single-color")]
     BreathingSingleColour {
+        #[serde(rename = "colour", alias = "color")]
         col: Rgb,
     },
     Tail,
     Rave {
+        #[serde(rename = "colour", alias = "color")]
         col: Rgb,
     },
     Wave,
 }
 
 impl LightingMode {
+    pub const GLORIOUS_PALETTE: [Rgb; 7] = [
+        rgb!(0xFF0000),
+        rgb!(0xFFC400),
+        rgb!(0xFBFF00),
+        rgb!(0x00FF33),
+        rgb!(0x00FBFF),
+        rgb!(0x0004FF),
+        rgb!(0xE600FF),
+    ];
+
+    pub const SEAMLESS_BREATHING_PALETTE: [Rgb; 7] = Self::GLORIOUS_PALETTE;
+
+    /// Also requires one custom colour at the start
+    pub const BREATHING_PALETTE: [Rgb; 5] = [
+        rgb!(0x0AFF7D),
+        rgb!(0xFF600A),
+        rgb!(0x0AFFE5),
+        rgb!(0xFF0AD4),
+        rgb!(0xFF0000),
+    ];
+
+    pub const TAIL_PALETTE: [Rgb; 6] = [
+        rgb!(0xFFF60A),
+        rgb!(0x0AFF7D),
+        rgb!(0xFF600A),
+        rgb!(0x0AFFE5),
+        rgb!(0xFF0AD4),
+        rgb!(0xFF0000),
+    ];
+
+    /// Also requires one custom colour at the start
+    pub const RAVE_PALETTE: [Rgb; 1] = [rgb!(0xFFFF00)];
+
+    pub const WAVE_PALETTE: [Rgb; 7] = [
+        rgb!(0xFFF60A),
+        rgb!(0x0AFF7D),
+        rgb!(0xFF600A),
+        rgb!(0x0AFFE5),
+        rgb!(0xFF0AD4),
+        rgb!(0x000000),
+        rgb!(0xFF0000),
+    ];
+
     /// Returns the associated ID for HID reports
     pub fn mode_id(&self) -> u8 {
         match self {
@@ -45,6 +99,65 @@ impl LightingMode {
         }
     }
 
+    /// Returns the lighting mode with the given ID.
+    ///
+    /// `col` is used as the custom colour for modes that require one.
+    pub fn from_id(mode_id: u8, col: Rgb) -> Option<Self> {
+        Some(match mode_id {
+            0x00 => LightingMode::Off,
+            0x01 => LightingMode::Glorious,
+            0x02 => LightingMode::SeamlessBreathing,
+            0x03 => LightingMode::Breathing { col },
+            0x04 => LightingMode::SingleColour { col },
+            0x05 => LightingMode::BreathingSingleColour { col },
+            0x06 => LightingMode::Tail,
+            0x07 => LightingMode::Rave { col },
+            0x08 => LightingMode::Wave,
+            _ => return None,
+        })
+    }
+
+    /// Returns the custom colour of the given lighting mode, if it has one.
+    pub fn custom_colour(&self) -> Option<Rgb> {
+        match self {
+            LightingMode::Breathing { col }
+            | LightingMode::SingleColour { col }
+            | LightingMode::BreathingSingleColour { col }
+            | LightingMode::Rave { col } => Some(*col),
+            _ => None,
+        }
+    }
+
+    /// Returns the lighting mode with its custom colour replaced, if it has one.
+    pub fn with_custom_colour(self, col: Rgb) -> Self {
+        match self {
+            LightingMode::Breathing { col: _ } => LightingMode::Breathing { col },
+            LightingMode::SingleColour { col: _ } => LightingMode::SingleColour { col },
+            LightingMode::BreathingSingleColour { col: _ } => {
+                LightingMode::BreathingSingleColour { col }
+            }
+            LightingMode::Rave { col: _ } => LightingMode::Rave { col },
+            mode => mode,
+        }
+    }
+
+    /// Returns the colours Glorious Core sends for the given lighting mode, starting with the custom
+    /// colour if it has one.
+    pub fn default_colours(&self) -> Vec<Rgb> {
+        let custom = self.custom_colour().into_iter();
+        match self {
+            LightingMode::Off => Vec::new(),
+            LightingMode::Glorious => Self::GLORIOUS_PALETTE.to_vec(),
+            LightingMode::SeamlessBreathing => Self::SEAMLESS_BREATHING_PALETTE.to_vec(),
+            LightingMode::Breathing { col: _ } => custom.chain(Self::BREATHING_PALETTE).collect(),
+            LightingMode::SingleColour { col: _ } => custom.collect(),
+            LightingMode::BreathingSingleColour { col: _ } => custom.collect(),
+            LightingMode::Tail => Self::TAIL_PALETTE.to_vec(),
+            LightingMode::Rave { col: _ } => custom.chain(Self::RAVE_PALETTE).collect(),
+            LightingMode::Wave => Self::WAVE_PALETTE.to_vec(),
+        }
+    }
+
     /// Returns the number of colours required by the given lighting mode
     pub fn num_colours(&self) -> u8 {
         match self {
@@ -60,3 +173,24 @@ impl LightingMode {
         }
     }
 }
+
+impl std::fmt::Display for LightingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LightingMode::Off => "off",
+            LightingMode::Glorious => "glorious",
+            LightingMode::SeamlessBreathing => "seamless-breathing",
+            LightingMode::Breathing { col: _ } => "breathing",
+            LightingMode::SingleColour { col: _ } => "single-colour",
+            LightingMode::BreathingSingleColour { col: _ } => "breathing-single-colour",
+            LightingMode::Tail => "tail",
+            LightingMode::Rave { col: _ } => "rave",
+            LightingMode::Wave => "wave",
+        };
+
+        match self.custom_colour() {
+            Some(col) => write!(f, "{name} {col}"),
+            None => write!(f, "{name}"),
+        }
+    }
+}
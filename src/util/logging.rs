@@ -0,0 +1,27 @@
+use log::LevelFilter;
+
+/// Returns the maximum level of log messages to print for the `-q` and `-v` flags.
+///
+/// `-v` shows debug messages and `-vv` traces every report, while `-q` only shows errors.
+pub fn level(quiet: bool, verbose: u8) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    }
+}
+
+/// Logs to stderr at the given level, unless overridden by RUST_LOG.
+///
+/// Timestamps are only shown when debugging, where they're precise enough to check the timing of
+/// reports.
+pub fn init(level: LevelFilter) {
+    let debugging = level >= LevelFilter::Debug;
+    env_logger::Builder::new()
+        .filter_level(level)
+        .format_timestamp(debugging.then_some(env_logger::TimestampPrecision::Micros))
+        .format_target(debugging)
+        .parse_default_env()
+        .init();
+}
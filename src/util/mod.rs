@@ -1,2 +1,4 @@
+pub mod capture;
 pub mod rgb;
 pub mod lighting_mode;
+pub mod logging;
@@ -1,16 +1,38 @@
 use std::{num::ParseIntError, str::FromStr};
 
+use crate::rgb;
+
+/// Errors from parsing an [`Rgb`], saying which form of colour couldn't be parsed.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ParseRgbError {
+    /// Hex colours must have 3 or 6 digits.
     LengthError,
+    /// A hex colour contained something other than hex digits.
     ParseIntError(ParseIntError),
+    /// An `rgb()`, `hsl()` or `hsv()` colour was invalid.
+    FunctionError {
+        function: &'static str,
+        reason: &'static str,
+    },
+    /// The colour wasn't hex, a colour function or a known colour name.
+    NameError { name: String },
 }
 
 impl std::fmt::Display for ParseRgbError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseRgbError::LengthError => write!(f, "RGB values must have length 6"),
-            ParseRgbError::ParseIntError(parse_int_error) => parse_int_error.fmt(f),
+            ParseRgbError::LengthError => write!(f, "Hex colours must have 3 or 6 digits"),
+            ParseRgbError::ParseIntError(parse_int_error) => {
+                write!(f, "Invalid hex colour: {parse_int_error}")
+            }
+            ParseRgbError::FunctionError { function, reason } => {
+                write!(f, "Invalid {function}() colour: {reason}")
+            }
+            ParseRgbError::NameError { name } => write!(
+                f,
+                "Unknown colour {name:?}, expected a CSS colour name, #RGB, #RRGGBB, rgb(), hsl() \
+                or hsv()"
+            ),
         }
     }
 }
@@ -23,11 +45,172 @@ impl From<ParseIntError> for ParseRgbError {
     }
 }
 
+/// Colour names from CSS, sorted by name.
+const NAMED_COLOURS: [(&str, Rgb); 148] = [
+    ("aliceblue", rgb!(0xF0F8FF)),
+    ("antiquewhite", rgb!(0xFAEBD7)),
+    ("aqua", rgb!(0x00FFFF)),
+    ("aquamarine", rgb!(0x7FFFD4)),
+    ("azure", rgb!(0xF0FFFF)),
+    ("beige", rgb!(0xF5F5DC)),
+    ("bisque", rgb!(0xFFE4C4)),
+    ("black", rgb!(0x000000)),
+    ("blanchedalmond", rgb!(0xFFEBCD)),
+    ("blue", rgb!(0x0000FF)),
+    ("blueviolet", rgb!(0x8A2BE2)),
+    ("brown", rgb!(0xA52A2A)),
+    ("burlywood", rgb!(0xDEB887)),
+    ("cadetblue", rgb!(0x5F9EA0)),
+    ("chartreuse", rgb!(0x7FFF00)),
+    ("chocolate", rgb!(0xD2691E)),
+    ("coral", rgb!(0xFF7F50)),
+    ("cornflowerblue", rgb!(0x6495ED)),
+    ("cornsilk", rgb!(0xFFF8DC)),
+    ("crimson", rgb!(0xDC143C)),
+    ("cyan", rgb!(0x00FFFF)),
+    ("darkblue", rgb!(0x00008B)),
+    ("darkcyan", rgb!(0x008B8B)),
+    ("darkgoldenrod", rgb!(0xB8860B)),
+    ("darkgray", rgb!(0xA9A9A9)),
+    ("darkgreen", rgb!(0x006400)),
+    ("darkgrey", rgb!(0xA9A9A9)),
+    ("darkkhaki", rgb!(0xBDB76B)),
+    ("darkmagenta", rgb!(0x8B008B)),
+    ("darkolivegreen", rgb!(0x556B2F)),
+    ("darkorange", rgb!(0xFF8C00)),
+    ("darkorchid", rgb!(0x9932CC)),
+    ("darkred", rgb!(0x8B0000)),
+    ("darksalmon", rgb!(0xE9967A)),
+    ("darkseagreen", rgb!(0x8FBC8F)),
+    ("darkslateblue", rgb!(0x483D8B)),
+    ("darkslategray", rgb!(0x2F4F4F)),
+    ("darkslategrey", rgb!(0x2F4F4F)),
+    ("darkturquoise", rgb!(0x00CED1)),
+    ("darkviolet", rgb!(0x9400D3)),
+    ("deeppink", rgb!(0xFF1493)),
+    ("deepskyblue", rgb!(0x00BFFF)),
+    ("dimgray", rgb!(0x696969)),
+    ("dimgrey", rgb!(0x696969)),
+    ("dodgerblue", rgb!(0x1E90FF)),
+    ("firebrick", rgb!(0xB22222)),
+    ("floralwhite", rgb!(0xFFFAF0)),
+    ("forestgreen", rgb!(0x228B22)),
+    ("fuchsia", rgb!(0xFF00FF)),
+    ("gainsboro", rgb!(0xDCDCDC)),
+    ("ghostwhite", rgb!(0xF8F8FF)),
+    ("gold", rgb!(0xFFD700)),
+    ("goldenrod", rgb!(0xDAA520)),
+    ("gray", rgb!(0x808080)),
+    ("green", rgb!(0x008000)),
+    ("greenyellow", rgb!(0xADFF2F)),
+    ("grey", rgb!(0x808080)),
+    ("honeydew", rgb!(0xF0FFF0)),
+    ("hotpink", rgb!(0xFF69B4)),
+    ("indianred", rgb!(0xCD5C5C)),
+    ("indigo", rgb!(0x4B0082)),
+    ("ivory", rgb!(0xFFFFF0)),
+    ("khaki", rgb!(0xF0E68C)),
+    ("lavender", rgb!(0xE6E6FA)),
+    ("lavenderblush", rgb!(0xFFF0F5)),
+    ("lawngreen", rgb!(0x7CFC00)),
+    ("lemonchiffon", rgb!(0xFFFACD)),
+    ("lightblue", rgb!(0xADD8E6)),
+    ("lightcoral", rgb!(0xF08080)),
+    ("lightcyan", rgb!(0xE0FFFF)),
+    ("lightgoldenrodyellow", rgb!(0xFAFAD2)),
+    ("lightgray", rgb!(0xD3D3D3)),
+    ("lightgreen", rgb!(0x90EE90)),
+    ("lightgrey", rgb!(0xD3D3D3)),
+    ("lightpink", rgb!(0xFFB6C1)),
+    ("lightsalmon", rgb!(0xFFA07A)),
+    ("lightseagreen", rgb!(0x20B2AA)),
+    ("lightskyblue", rgb!(0x87CEFA)),
+    ("lightslategray", rgb!(0x778899)),
+    ("lightslategrey", rgb!(0x778899)),
+    ("lightsteelblue", rgb!(0xB0C4DE)),
+    ("lightyellow", rgb!(0xFFFFE0)),
+    ("lime", rgb!(0x00FF00)),
+    ("limegreen", rgb!(0x32CD32)),
+    ("linen", rgb!(0xFAF0E6)),
+    ("magenta", rgb!(0xFF00FF)),
+    ("maroon", rgb!(0x800000)),
+    ("mediumaquamarine", rgb!(0x66CDAA)),
+    ("mediumblue", rgb!(0x0000CD)),
+    ("mediumorchid", rgb!(0xBA55D3)),
+    ("mediumpurple", rgb!(0x9370DB)),
+    ("mediumseagreen", rgb!(0x3CB371)),
+    ("mediumslateblue", rgb!(0x7B68EE)),
+    ("mediumspringgreen", rgb!(0x00FA9A)),
+    ("mediumturquoise", rgb!(0x48D1CC)),
+    ("mediumvioletred", rgb!(0xC71585)),
+    ("midnightblue", rgb!(0x191970)),
+    ("mintcream", rgb!(0xF5FFFA)),
+    ("mistyrose", rgb!(0xFFE4E1)),
+    ("moccasin", rgb!(0xFFE4B5)),
+    ("navajowhite", rgb!(0xFFDEAD)),
+    ("navy", rgb!(0x000080)),
+    ("oldlace", rgb!(0xFDF5E6)),
+    ("olive", rgb!(0x808000)),
+    ("olivedrab", rgb!(0x6B8E23)),
+    ("orange", rgb!(0xFFA500)),
+    ("orangered", rgb!(0xFF4500)),
+    ("orchid", rgb!(0xDA70D6)),
+    ("palegoldenrod", rgb!(0xEEE8AA)),
+    ("palegreen", rgb!(0x98FB98)),
+    ("paleturquoise", rgb!(0xAFEEEE)),
+    ("palevioletred", rgb!(0xDB7093)),
+    ("papayawhip", rgb!(0xFFEFD5)),
+    ("peachpuff", rgb!(0xFFDAB9)),
+    ("peru", rgb!(0xCD853F)),
+    ("pink", rgb!(0xFFC0CB)),
+    ("plum", rgb!(0xDDA0DD)),
+    ("powderblue", rgb!(0xB0E0E6)),
+    ("purple", rgb!(0x800080)),
+    ("rebeccapurple", rgb!(0x663399)),
+    ("red", rgb!(0xFF0000)),
+    ("rosybrown", rgb!(0xBC8F8F)),
+    ("royalblue", rgb!(0x4169E1)),
+    ("saddlebrown", rgb!(0x8B4513)),
+    ("salmon", rgb!(0xFA8072)),
+    ("sandybrown", rgb!(0xF4A460)),
+    ("seagreen", rgb!(0x2E8B57)),
+    ("seashell", rgb!(0xFFF5EE)),
+    ("sienna", rgb!(0xA0522D)),
+    ("silver", rgb!(0xC0C0C0)),
+    ("skyblue", rgb!(0x87CEEB)),
+    ("slateblue", rgb!(0x6A5ACD)),
+    ("slategray", rgb!(0x708090)),
+    ("slategrey", rgb!(0x708090)),
+    ("snow", rgb!(0xFFFAFA)),
+    ("springgreen", rgb!(0x00FF7F)),
+    ("steelblue", rgb!(0x4682B4)),
+    ("tan", rgb!(0xD2B48C)),
+    ("teal", rgb!(0x008080)),
+    ("thistle", rgb!(0xD8BFD8)),
+    ("tomato", rgb!(0xFF6347)),
+    ("turquoise", rgb!(0x40E0D0)),
+    ("violet", rgb!(0xEE82EE)),
+    ("wheat", rgb!(0xF5DEB3)),
+    ("white", rgb!(0xFFFFFF)),
+    ("whitesmoke", rgb!(0xF5F5F5)),
+    ("yellow", rgb!(0xFFFF00)),
+    ("yellowgreen", rgb!(0x9ACD32)),
+];
+
 /// RGB value, represented as three u8s.
-/// Example:
+///
+/// Can be parsed from a CSS colour name, `#RGB`, `#RRGGBB` (the `#` is optional), `rgb(r, g, b)`,
+/// `hsl(h, s%, l%)` or `hsv(h, s%, v%)`. Example:
 /// ```
+/// # use i2control::util::rgb::Rgb;
 /// let rgb: Rgb = "#FF0005".parse().unwrap();
 /// println!("{rgb}, {rgb:?}"); // #FF0005, Rgb { r: 255, g: 0, b: 5 }
+///
+/// assert_eq!("rebeccapurple".parse(), Ok(Rgb::new(0x66, 0x33, 0x99)));
+/// assert_eq!("#F05".parse(), Ok(Rgb::new(0xFF, 0x00, 0x55)));
+/// assert_eq!("rgb(255, 0, 5)".parse(), Ok(Rgb::new(255, 0, 5)));
+/// assert_eq!("hsl(120, 100%, 25%)".parse(), Ok(Rgb::new(0, 128, 0)));
+/// assert_eq!("hsv(240deg 100% 50%)".parse(), Ok(Rgb::new(0, 0, 128)));
 /// ```
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Rgb {
@@ -36,6 +219,11 @@ pub struct Rgb {
     b: u8,
 }
 
+/// Converts a value between 0 and 1 to a channel, clamping values outside that range.
+fn to_channel(x: f32) -> u8 {
+    (x.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
 impl Rgb {
     pub const fn new(r: u8, g: u8, b: u8) -> Self {
         Rgb { r, g, b }
@@ -44,11 +232,160 @@ impl Rgb {
     pub const fn bytes(&self) -> [u8; 3] {
         [self.r, self.g, self.b]
     }
+
+    /// Returns the CSS colour with the given name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        NAMED_COLOURS
+            .binary_search_by_key(&name.as_str(), |(n, _)| n)
+            .ok()
+            .map(|i| NAMED_COLOURS[i].1)
+    }
+
+    /// Creates a colour from a hue in degrees, and saturation and value between 0 and 1.
+    ///
+    /// The hue wraps around, and the saturation and value are clamped.
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let channel = |n: f32| {
+            let k = (n + h.rem_euclid(360.0) / 60.0) % 6.0;
+            to_channel(v - v * s * k.min(4.0 - k).clamp(0.0, 1.0))
+        };
+
+        Self::new(channel(5.0), channel(3.0), channel(1.0))
+    }
+
+    /// Creates a colour from a hue in degrees, and saturation and lightness between 0 and 1.
+    ///
+    /// The hue wraps around, and the saturation and lightness are clamped.
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Self {
+        let (s, l) = (s.clamp(0.0, 1.0), l.clamp(0.0, 1.0));
+        let v = l + s * l.min(1.0 - l);
+        let s = if v == 0.0 { 0.0 } else { 2.0 * (1.0 - l / v) };
+        Self::from_hsv(h, s, v)
+    }
+
+    /// Returns the hue in degrees (from 0 up to 360), and the saturation and value between 0 and 1.
+    /// Example:
+    /// ```
+    /// # use i2control::util::rgb::Rgb;
+    /// let (h, s, v) = Rgb::new(0, 0, 255).to_hsv();
+    /// assert_eq!((h, s, v), (240.0, 1.0, 1.0));
+    /// assert_eq!(Rgb::from_hsv(h, s, v), Rgb::new(0, 0, 255));
+    /// ```
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let [r, g, b] = self.bytes().map(|c| c as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let range = max - r.min(g).min(b);
+
+        let h = if range == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / range)
+        } else if max == g {
+            60.0 * ((b - r) / range + 2.0)
+        } else {
+            60.0 * ((r - g) / range + 4.0)
+        };
+        let s = if max == 0.0 { 0.0 } else { range / max };
+
+        (h.rem_euclid(360.0), s, max)
+    }
+
+    /// Interpolates between this colour and `other`, where `t` is clamped between 0 (this colour)
+    /// and 1 (`other`). Example:
+    /// ```
+    /// # use i2control::util::rgb::Rgb;
+    /// let black = Rgb::new(0, 0, 0);
+    /// let white = Rgb::new(255, 255, 255);
+    /// assert_eq!(black.lerp(white, 0.5), Rgb::new(128, 128, 128));
+    /// ```
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let [r, g, b] = [0, 1, 2].map(|i| {
+            let (a, b) = (self.bytes()[i] as f32, other.bytes()[i] as f32);
+            to_channel((a + (b - a) * t) / 255.0)
+        });
+
+        Self::new(r, g, b)
+    }
+
+    /// Scales the brightness of the colour by `factor`, clamping each channel. Example:
+    /// ```
+    /// # use i2control::util::rgb::Rgb;
+    /// assert_eq!(Rgb::new(255, 128, 0).scale(0.5), Rgb::new(128, 64, 0));
+    /// ```
+    pub fn scale(self, factor: f32) -> Self {
+        let [r, g, b] = self
+            .bytes()
+            .map(|c| to_channel(c as f32 / 255.0 * factor.max(0.0)));
+        Self::new(r, g, b)
+    }
+
+    /// Parses `#RGB` or `#RRGGBB`, with an optional `#`.
+    fn from_hex(hex: &str) -> Result<Self, ParseRgbError> {
+        let digits: Vec<char> = hex.chars().collect();
+        let pairs: Vec<String> = match digits.len() {
+            3 => digits
+                .iter()
+                .map(|c| [c, c].into_iter().collect())
+                .collect(),
+            6 => digits.chunks(2).map(|p| p.iter().collect()).collect(),
+            _ => return Err(ParseRgbError::LengthError),
+        };
+
+        let channel = |i: usize| u8::from_str_radix(&pairs[i], 16);
+        Ok(Self::new(channel(0)?, channel(1)?, channel(2)?))
+    }
+
+    /// Parses the arguments of an `rgb()`, `hsl()` or `hsv()` colour.
+    fn from_function(function: &'static str, args: &str) -> Result<Self, ParseRgbError> {
+        let error = |reason| ParseRgbError::FunctionError { function, reason };
+
+        let args: Vec<_> = args
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|a| !a.is_empty())
+            .collect();
+        let [a, b, c] = args[..] else {
+            return Err(error("expected 3 values"));
+        };
+
+        let number = |arg: &str| arg.parse::<f32>().map_err(|_| error("invalid number"));
+        // Percentages may be given without the %, but are always out of 100.
+        let percent = |arg: &str| {
+            let p = number(arg.strip_suffix('%').unwrap_or(arg))?;
+            match p {
+                0.0..=100.0 => Ok(p / 100.0),
+                _ => Err(error("percentages must be between 0 and 100")),
+            }
+        };
+        let hue = |arg: &str| number(arg.strip_suffix("deg").unwrap_or(arg));
+
+        match function {
+            "rgb" => {
+                let channel = |arg: &str| match arg.strip_suffix('%') {
+                    Some(_) => percent(arg),
+                    None => match number(arg)? {
+                        c @ 0.0..=255.0 => Ok(c / 255.0),
+                        _ => Err(error("values must be between 0 and 255")),
+                    },
+                };
+                Ok(Self::new(
+                    to_channel(channel(a)?),
+                    to_channel(channel(b)?),
+                    to_channel(channel(c)?),
+                ))
+            }
+            "hsl" => Ok(Self::from_hsl(hue(a)?, percent(b)?, percent(c)?)),
+            _ => Ok(Self::from_hsv(hue(a)?, percent(b)?, percent(c)?)),
+        }
+    }
 }
 
 /// Creates a new RGB value from the given values.
 /// Example:
 /// ```
+/// # use i2control::{rgb, util::rgb::Rgb};
 /// let col1 = rgb!(0xFF, 0x00, 0x50);
 /// let col2 = rgb!(0xDEADBE);
 /// println!("{col1}, {col2}"); // #FF0050, #DEADBE
@@ -78,16 +415,52 @@ impl FromStr for Rgb {
     type Err = ParseRgbError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.trim_start_matches("#");
-
-        if s.len() == 6 {
-            Ok(Self {
-                r: u8::from_str_radix(&s[..2], 16)?,
-                g: u8::from_str_radix(&s[2..4], 16)?,
-                b: u8::from_str_radix(&s[4..], 16)?,
-            })
-        } else {
-            Err(ParseRgbError::LengthError)
+        let s = s.trim();
+
+        if let Some(hex) = s.strip_prefix('#') {
+            return Self::from_hex(hex);
+        }
+
+        let lower = s.to_ascii_lowercase();
+        for function in ["rgb", "hsl", "hsv"] {
+            if let Some(args) = lower.strip_prefix(function) {
+                let args = args
+                    .trim_start()
+                    .strip_prefix('(')
+                    .and_then(|a| a.strip_suffix(')'))
+                    .ok_or(ParseRgbError::FunctionError {
+                        function,
+                        reason: "expected values in brackets",
+                    })?;
+                return Self::from_function(function, args);
+            }
         }
+
+        if let Some(rgb) = Self::from_name(s) {
+            return Ok(rgb);
+        }
+
+        // Hex without a #
+        if s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Self::from_hex(s);
+        }
+
+        Err(ParseRgbError::NameError {
+            name: s.to_string(),
+        })
+    }
+}
+
+/// RGB values are serialised in the same `#RRGGBB` format they're displayed in.
+impl serde::Serialize for Rgb {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Rgb {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
     }
 }
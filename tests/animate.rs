@@ -0,0 +1,76 @@
+//! Checks the messages sent when playing lighting effects.
+
+use std::time::Duration;
+
+use i2control::{
+    device::{
+        lighting::{
+            LightingSettings, RATE_RANGE,
+            animate::{Gradient, Pulse, play},
+        },
+        transport::Recorder,
+    },
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+/// Returns the reports of a single colour message for each colour.
+fn single_colours(colours: &[Rgb]) -> Vec<Vec<u8>> {
+    colours
+        .iter()
+        .flat_map(|col| {
+            let mode = LightingMode::SingleColour { col: *col };
+            let settings = LightingSettings::new(mode, 0x14, *RATE_RANGE.start());
+            settings.message().unwrap().to_bytes()
+        })
+        .collect()
+}
+
+fn grey(level: u8) -> Rgb {
+    Rgb::new(level, level, level)
+}
+
+#[test]
+fn gradient() {
+    let recorder = Recorder::new();
+    let mut effect = Gradient {
+        from: grey(0),
+        to: grey(255),
+        period: Duration::from_secs(1),
+    };
+
+    let sent = play(
+        &recorder,
+        &mut effect,
+        0x14,
+        4.0,
+        Some(Duration::from_secs(1)),
+    )
+    .unwrap();
+    assert_eq!(sent, 4);
+    assert_eq!(
+        recorder.sent(),
+        single_colours(&[grey(0), grey(128), grey(255), grey(128)])
+    );
+}
+
+#[test]
+fn pulse() {
+    let recorder = Recorder::new();
+    let mut effect = Pulse::new(grey(0), grey(255), Duration::from_millis(500));
+    effect.trigger().pulse();
+
+    // Frames after the pulse has faded are the same colour, so aren't sent.
+    let sent = play(
+        &recorder,
+        &mut effect,
+        0x14,
+        8.0,
+        Some(Duration::from_secs(1)),
+    )
+    .unwrap();
+    assert_eq!(sent, 5);
+    assert_eq!(
+        recorder.sent(),
+        single_colours(&[grey(255), grey(191), grey(128), grey(64), grey(0)])
+    );
+}
@@ -0,0 +1,64 @@
+//! Checks the events reported while monitoring the battery.
+
+use i2control::device::battery::{BatteryEvent, BatteryMonitor, BatteryStatus};
+
+fn discharging(percentage: u8) -> BatteryStatus {
+    BatteryStatus {
+        percentage,
+        charging: false,
+    }
+}
+
+fn charging(percentage: u8) -> BatteryStatus {
+    BatteryStatus {
+        percentage,
+        charging: true,
+    }
+}
+
+#[test]
+fn thresholds() {
+    let mut monitor = BatteryMonitor::new(vec![20, 5]);
+
+    assert_eq!(monitor.update(discharging(50)), []);
+    assert_eq!(monitor.update(discharging(20)), []);
+    assert_eq!(
+        monitor.update(discharging(19)),
+        [BatteryEvent::Low { threshold: 20 }]
+    );
+    assert_eq!(monitor.update(discharging(10)), []);
+    assert_eq!(
+        monitor.update(discharging(4)),
+        [BatteryEvent::Low { threshold: 5 }]
+    );
+    assert_eq!(monitor.update(discharging(3)), []);
+}
+
+#[test]
+fn lowest_threshold_crossed() {
+    let mut monitor = BatteryMonitor::new(vec![20, 5]);
+    assert_eq!(
+        monitor.update(discharging(3)),
+        [BatteryEvent::Low { threshold: 5 }]
+    );
+}
+
+#[test]
+fn charging_changes() {
+    let mut monitor = BatteryMonitor::new(vec![20]);
+
+    assert_eq!(monitor.update(charging(15)), []);
+    assert_eq!(monitor.update(charging(25)), []);
+    assert_eq!(
+        monitor.update(discharging(25)),
+        [BatteryEvent::ChargingStopped]
+    );
+    assert_eq!(
+        monitor.update(discharging(19)),
+        [BatteryEvent::Low { threshold: 20 }]
+    );
+    assert_eq!(
+        monitor.update(charging(19)),
+        [BatteryEvent::ChargingStarted]
+    );
+}
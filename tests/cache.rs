@@ -0,0 +1,130 @@
+//! Checks how the last DPI settings sent are cached and used as the current settings.
+
+use std::{env, fs, process};
+
+#[cfg(not(feature = "unverified-reads"))]
+use i2control::device::{
+    dpi::{DpiUpdate, update_dpi},
+    transport::Recorder,
+};
+use i2control::{
+    cache::DpiCache,
+    device::dpi::{DpiSettings, DpiStage, StageColour},
+};
+#[cfg(not(feature = "unverified-reads"))]
+use i2control::{rgb, util::rgb::Rgb};
+
+/// Returns an empty cache in a directory which doesn't exist yet.
+fn cache(name: &str) -> DpiCache {
+    let dir = env::temp_dir().join(format!("i2control-test-{}-{name}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    DpiCache::new(dir.join("state").join("dpi.toml"))
+}
+
+fn current() -> DpiSettings {
+    DpiSettings {
+        active_stage: 1,
+        stages: vec![
+            DpiStage::new(800),
+            DpiStage {
+                dpi: 1600,
+                colour: StageColour::Blue.rgb(),
+            },
+        ],
+        lift_off_distance: 2,
+        debounce_time: 4,
+        polling_rate: 1000,
+    }
+}
+
+#[test]
+fn store_and_load() {
+    let cache = cache("store");
+    assert_eq!(cache.load(), None);
+
+    cache.store(&current());
+    assert_eq!(cache.load(), Some(current()));
+}
+
+#[test]
+fn read_only() {
+    let cache = cache("read-only");
+    cache.store(&current());
+
+    let read_only = cache.read_only();
+    read_only.store(&DpiSettings::default());
+    assert_eq!(read_only.load(), Some(current()));
+}
+
+#[test]
+fn invalid() {
+    let cache = cache("invalid");
+    fs::create_dir_all(cache.path().parent().unwrap()).unwrap();
+    fs::write(cache.path(), "stages = 800").unwrap();
+    assert_eq!(cache.load(), None);
+}
+
+/// Without reading the current settings, the cached settings are kept, and the cache is updated
+/// with the settings sent.
+#[cfg(not(feature = "unverified-reads"))]
+#[test]
+fn partial_update() {
+    let cache = cache("update");
+    cache.store(&current());
+
+    let update = DpiUpdate {
+        polling_rate: Some(500),
+        ..Default::default()
+    };
+    let recorder = Recorder::new();
+    let settings = update_dpi(&recorder, &update, Some(&cache)).unwrap();
+
+    let expected = DpiSettings {
+        polling_rate: 500,
+        ..current()
+    };
+    assert_eq!(settings, expected);
+    assert_eq!(recorder.sent(), expected.message().unwrap().to_bytes());
+    assert_eq!(cache.load(), Some(expected));
+}
+
+/// A colour which hasn't been captured is accepted for a stage which was sent with it, e.g. by
+/// applying an exported config.
+#[cfg(not(feature = "unverified-reads"))]
+#[test]
+fn uncaptured_colour() {
+    let cache = cache("uncaptured");
+    let mut settings = current();
+    settings.stages[1].colour = rgb!(0xFF0000);
+    cache.store(&settings);
+
+    let update = DpiUpdate {
+        stages: Some(vec![400, 800]),
+        colours: Some(vec![StageColour::Blue, StageColour::Other(rgb!(0xFF0000))]),
+        ..Default::default()
+    };
+    let sent = update_dpi(&Recorder::new(), &update, Some(&cache)).unwrap();
+    assert_eq!(sent.stages[1].colour, rgb!(0xFF0000));
+
+    // But not for another stage.
+    let update = DpiUpdate {
+        colours: Some(vec![StageColour::Other(rgb!(0xFF0000))]),
+        ..Default::default()
+    };
+    assert!(update_dpi(&Recorder::new(), &update, Some(&cache)).is_err());
+}
+
+/// Nothing is cached if the settings can't be sent.
+#[cfg(not(feature = "unverified-reads"))]
+#[test]
+fn invalid_update() {
+    let cache = cache("invalid-update");
+    cache.store(&current());
+
+    let update = DpiUpdate {
+        active_stage: Some(2),
+        ..Default::default()
+    };
+    assert!(update_dpi(&Recorder::new(), &update, Some(&cache)).is_err());
+    assert_eq!(cache.load(), Some(current()));
+}
@@ -0,0 +1,158 @@
+//! Checks which configs are applied, and what is sent for them.
+
+use i2control::{
+    config::{Config, apply_config},
+    device::{
+        dpi::{DpiSettings, DpiStage, StageIndexError},
+        lighting::LightingSettings,
+        timeout::Timeout,
+        transport::Recorder,
+    },
+    error::ValidationError,
+    rgb,
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+#[test]
+fn apply() {
+    let config: Config = r##"
+        timeout = 10
+
+        [lighting]
+        mode = "breathing"
+        colour = "#FF0000"
+        brightness = 20
+        rate = 12
+        wireless_brightness = 5
+
+        [dpi]
+        active_stage = 1
+        stages = [800, 1600]
+        colours = ["#FFA40D", "#26B4FF"]
+        lift_off_distance = 1
+        debounce_time = 2
+        polling_rate = 1000
+    "##
+    .parse()
+    .unwrap();
+
+    let lighting = LightingSettings::new(
+        LightingMode::Breathing {
+            col: rgb!(0xFF0000),
+        },
+        0x14,
+        0x0C,
+    )
+    .with_wireless(0x05, 0x0C);
+    let dpi = DpiSettings {
+        active_stage: 1,
+        stages: vec![
+            DpiStage::new(800),
+            DpiStage {
+                dpi: 1600,
+                colour: rgb!(0x26B4FF),
+            },
+        ],
+        ..Default::default()
+    };
+
+    // Complete DPI sections are sent without reading the current settings.
+    let recorder = Recorder::new();
+    apply_config(&recorder, &config, None).unwrap();
+    let expected = [
+        lighting.message().unwrap().to_bytes(),
+        dpi.message().unwrap().to_bytes(),
+        Timeout::Minutes(10).message().unwrap().to_bytes(),
+    ]
+    .concat();
+    assert_eq!(recorder.sent(), expected);
+}
+
+/// Stage indices and colours are checked against the stages before anything is sent.
+#[test]
+fn missing_stage() {
+    for dpi in [
+        "active_stage = 3",
+        "active_stage = 0\ncolours = [\"#FFA40D\", \"#26B4FF\"]",
+    ] {
+        let config: Config = format!(
+            "
+            [lighting]
+            mode = \"off\"
+            brightness = 0
+            rate = 1
+
+            [dpi]
+            stages = [800]
+            lift_off_distance = 1
+            debounce_time = 2
+            polling_rate = 1000
+            {dpi}
+            "
+        )
+        .parse()
+        .unwrap();
+
+        assert!(matches!(
+            config.validate(),
+            Err(ValidationError::StageIndex(StageIndexError {
+                index: _,
+                num_stages: 1
+            }))
+        ));
+
+        let recorder = Recorder::new();
+        assert!(apply_config(&recorder, &config, None).is_err());
+        assert!(recorder.sent().is_empty());
+    }
+}
+
+/// Without reading the current settings or a cache, DPI settings which aren't given are reset to
+/// their defaults.
+#[cfg(not(feature = "unverified-reads"))]
+#[test]
+fn partial_dpi() {
+    let config: Config = r#"
+        [dpi]
+        polling_rate = 500
+    "#
+    .parse()
+    .unwrap();
+
+    let recorder = Recorder::new();
+    apply_config(&recorder, &config, None).unwrap();
+
+    let dpi = DpiSettings {
+        polling_rate: 500,
+        ..Default::default()
+    };
+    assert_eq!(recorder.sent(), dpi.message().unwrap().to_bytes());
+}
+
+/// Colours which haven't been captured are rejected before anything is sent, unless the stage
+/// already has them.
+#[cfg(not(feature = "unverified-reads"))]
+#[test]
+fn uncaptured_colour() {
+    let config: Config = r##"
+        [lighting]
+        mode = "off"
+        brightness = 0
+        rate = 1
+
+        [dpi]
+        stages = [800]
+        colours = ["#FF0000"]
+    "##
+    .parse()
+    .unwrap();
+
+    let recorder = Recorder::new();
+    assert!(matches!(
+        apply_config(&recorder, &config, None),
+        Err(i2control::Error::Validation(ValidationError::StageColour(
+            _
+        )))
+    ));
+    assert!(recorder.sent().is_empty());
+}
@@ -0,0 +1,75 @@
+//! Checks which device is picked when several candidates are connected.
+
+use std::ffi::CString;
+
+use i2control::device::discovery::{
+    Candidate, ConnectionMode, DiscoveryError, GLORIOUS_VID, I2_WIRED_PID, I2_WL_PID, known_device,
+    select_device,
+};
+
+fn candidate(path: &str, pid: u16) -> Candidate {
+    let model = known_device(GLORIOUS_VID, pid).unwrap();
+    Candidate::new(CString::new(path).unwrap(), model)
+}
+
+fn wired(path: &str) -> Candidate {
+    candidate(path, I2_WIRED_PID)
+}
+
+fn wireless(path: &str) -> Candidate {
+    candidate(path, I2_WL_PID)
+}
+
+#[test]
+fn no_devices() {
+    assert!(matches!(
+        select_device(Vec::new(), None),
+        Err(DiscoveryError::NoDevices)
+    ));
+}
+
+#[test]
+fn single_device() {
+    let selected = select_device(vec![wireless("a")], None).unwrap();
+    assert_eq!(selected.path().to_str(), Ok("a"));
+    assert_eq!(selected.mode(), ConnectionMode::Wireless);
+}
+
+#[test]
+fn wired_and_dongle() {
+    // The same mouse charging over the cable while its dongle is still plugged in
+    for candidates in [
+        vec![wireless("a"), wired("b")],
+        vec![wired("b"), wireless("a")],
+    ] {
+        let selected = select_device(candidates, None).unwrap();
+        assert_eq!(selected.path().to_str(), Ok("b"));
+        assert_eq!(selected.mode(), ConnectionMode::Wired);
+    }
+}
+
+#[test]
+fn same_mode() {
+    let result = select_device(vec![wireless("a"), wireless("b")], None);
+    assert!(matches!(
+        result,
+        Err(DiscoveryError::MultipleDevices(candidates)) if candidates.len() == 2
+    ));
+}
+
+#[test]
+fn index() {
+    let candidates = || vec![wireless("a"), wired("b")];
+
+    let selected = select_device(candidates(), Some(0)).unwrap();
+    assert_eq!(selected.path().to_str(), Ok("a"));
+
+    assert!(matches!(
+        select_device(candidates(), Some(2)),
+        Err(DiscoveryError::InvalidIndex { index: 2, len: 2 })
+    ));
+    assert!(matches!(
+        select_device(Vec::new(), Some(0)),
+        Err(DiscoveryError::InvalidIndex { index: 0, len: 0 })
+    ));
+}
@@ -0,0 +1,181 @@
+//! Checks how DPI updates change the current settings.
+
+use i2control::{
+    device::{
+        dpi::{DpiSettings, DpiStage, DpiUpdate, StageColour, StageIndexError, update_dpi},
+        transport::Recorder,
+    },
+    rgb,
+    util::rgb::Rgb,
+};
+
+const BLUE: Rgb = StageColour::Blue.rgb();
+
+fn current() -> DpiSettings {
+    DpiSettings {
+        active_stage: 2,
+        stages: vec![
+            DpiStage::new(400),
+            DpiStage {
+                dpi: 800,
+                colour: BLUE,
+            },
+            DpiStage::new(1600),
+        ],
+        lift_off_distance: 2,
+        debounce_time: 4,
+        polling_rate: 500,
+    }
+}
+
+#[test]
+fn unspecified_kept() {
+    let update = DpiUpdate {
+        polling_rate: Some(1000),
+        ..Default::default()
+    };
+
+    let mut settings = current();
+    update.apply(&mut settings).unwrap();
+    assert_eq!(
+        settings,
+        DpiSettings {
+            polling_rate: 1000,
+            ..current()
+        }
+    );
+}
+
+#[test]
+fn stages_replaced() {
+    // The active stage no longer exists, so the first stage is made active. The remaining stages
+    // keep their colours.
+    let update = DpiUpdate {
+        stages: Some(vec![1000, 2000]),
+        ..Default::default()
+    };
+
+    let mut settings = current();
+    update.apply(&mut settings).unwrap();
+    assert_eq!(settings.active_stage, 0);
+    assert_eq!(
+        settings.stages,
+        [
+            DpiStage::new(1000),
+            DpiStage {
+                dpi: 2000,
+                colour: BLUE,
+            },
+        ]
+    );
+    assert_eq!(settings.lift_off_distance, 2);
+
+    // Unless another stage is selected at the same time.
+    let update = DpiUpdate {
+        active_stage: Some(1),
+        ..update
+    };
+
+    let mut settings = current();
+    update.apply(&mut settings).unwrap();
+    assert_eq!(settings.active_stage, 1);
+}
+
+#[test]
+fn single_stage() {
+    let update = DpiUpdate {
+        active_stage: Some(0),
+        stage_dpi: Some((0, 450)),
+        stage_colour: Some((2, StageColour::Blue)),
+        ..Default::default()
+    };
+
+    let mut settings = current();
+    update.apply(&mut settings).unwrap();
+
+    let mut expected = current();
+    expected.active_stage = 0;
+    expected.stages[0].dpi = 450;
+    expected.stages[2].colour = BLUE;
+    assert_eq!(settings, expected);
+}
+
+#[test]
+fn colours() {
+    // Stages without a colour keep their current colour.
+    let update = DpiUpdate {
+        colours: Some(vec![StageColour::Blue, StageColour::Orange]),
+        ..Default::default()
+    };
+
+    let mut settings = current();
+    update.apply(&mut settings).unwrap();
+
+    let mut expected = current();
+    expected.stages[0].colour = BLUE;
+    expected.stages[1].colour = StageColour::Orange.rgb();
+    assert_eq!(settings, expected);
+}
+
+#[test]
+fn snap() {
+    assert_eq!(StageColour::nearest(rgb!(0x0000FF)), StageColour::Blue);
+    assert_eq!(StageColour::nearest(rgb!(0xFF0000)), StageColour::Orange);
+    assert_eq!(StageColour::nearest(BLUE), StageColour::Blue);
+    assert!(StageColour::try_from(rgb!(0x0000FF)).is_err());
+}
+
+#[test]
+fn missing_stage() {
+    let updates = [
+        DpiUpdate {
+            active_stage: Some(3),
+            ..Default::default()
+        },
+        DpiUpdate {
+            stage_dpi: Some((3, 800)),
+            ..Default::default()
+        },
+        DpiUpdate {
+            stage_colour: Some((3, StageColour::Blue)),
+            ..Default::default()
+        },
+        DpiUpdate {
+            colours: Some(vec![StageColour::Blue; 4]),
+            ..Default::default()
+        },
+    ];
+
+    for update in updates {
+        let result = update.apply(&mut current());
+        assert!(
+            matches!(
+                result,
+                Err(StageIndexError {
+                    index: 3,
+                    num_stages: 3
+                })
+            ),
+            "{update:?}"
+        );
+    }
+}
+
+#[test]
+fn complete_update() {
+    let update = DpiUpdate {
+        active_stage: Some(1),
+        stages: Some(vec![800, 1600]),
+        lift_off_distance: Some(1),
+        debounce_time: Some(2),
+        polling_rate: Some(1000),
+        ..Default::default()
+    };
+
+    // Nothing needs to be read, so the settings are sent straight away.
+    let recorder = Recorder::new();
+    let settings = update_dpi(&recorder, &update, None).unwrap();
+    assert_eq!(settings.active_stage, 1);
+
+    assert_eq!(recorder.sent(), settings.message().unwrap().to_bytes());
+}
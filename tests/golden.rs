@@ -0,0 +1,209 @@
+//! Checks that messages match those sent by Glorious Core, as captured in `data/`.
+
+use std::path::Path;
+
+use i2control::{
+    device::{
+        decode::{Decoder, Operation},
+        dpi::{DpiSettings, DpiStage, set_dpi_settings},
+        lighting::LightingSettings,
+        message::{DecodeError, Message, REPORT_LEN, Report},
+        timeout::Timeout,
+        transport::Recorder,
+    },
+    rgb,
+    util::{capture::load_capture, lighting_mode::LightingMode, rgb::Rgb},
+};
+
+const ORANGE: Rgb = rgb!(0xFFA40D);
+const BLUE: Rgb = rgb!(0x26B4FF);
+
+fn capture(name: &str) -> Vec<Vec<u8>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("data")
+        .join(name);
+    let reports = load_capture(&dir).unwrap();
+    assert!(!reports.is_empty(), "No reports in {}", dir.display());
+    reports
+}
+
+/// Checks that each report matches the capture. Core pads reports to 64 bytes, but only the first
+/// [`REPORT_LEN`] are used.
+fn assert_reports_match(reports: &[Vec<u8>], name: &str) {
+    let expected = capture(name);
+    assert_eq!(
+        reports.len(),
+        expected.len(),
+        "Number of reports for {name}"
+    );
+
+    for (i, (report, expected)) in reports.iter().zip(expected).enumerate() {
+        let (used, padding) = expected.split_at(REPORT_LEN);
+        assert_eq!(report.as_slice(), used, "Report {} of {name}", i + 1);
+        assert!(
+            padding.iter().all(|b| *b == 0x00),
+            "Padding of report {} of {name}",
+            i + 1
+        );
+    }
+}
+
+fn assert_message_matches(message: &Message, name: &str) {
+    assert_reports_match(&message.to_bytes(), name);
+}
+
+fn dpi(stages: &[(u16, Rgb)], polling_rate: u16) -> DpiSettings {
+    DpiSettings {
+        active_stage: 0,
+        stages: stages
+            .iter()
+            .map(|&(dpi, colour)| DpiStage { dpi, colour })
+            .collect(),
+        lift_off_distance: 1,
+        debounce_time: 2,
+        polling_rate,
+    }
+}
+
+#[test]
+fn dpi_add_stage() {
+    let settings = dpi(&[(850, BLUE), (800, BLUE)], 1000);
+    assert_message_matches(&settings.message().unwrap(), "dpi/+800");
+
+    let settings = dpi(&[(800, ORANGE), (850, BLUE)], 1000);
+    assert_message_matches(&settings.message().unwrap(), "dpi/+850");
+}
+
+#[test]
+fn dpi_remove_stage() {
+    let settings = dpi(&[(850, BLUE)], 1000);
+    assert_message_matches(&settings.message().unwrap(), "dpi/-800");
+
+    let settings = dpi(&[(800, BLUE)], 1000);
+    assert_message_matches(&settings.message().unwrap(), "dpi/-850");
+}
+
+#[test]
+fn dpi_rounding() {
+    let settings = dpi(&[(824, BLUE)], 1000);
+    assert_message_matches(&settings.message().unwrap(), "dpi/-850");
+}
+
+#[test]
+fn polling_rate() {
+    for polling_rate in [250, 500, 1000] {
+        let settings = dpi(&[(800, BLUE)], polling_rate);
+        assert_message_matches(
+            &settings.message().unwrap(),
+            &format!("polling/{polling_rate}Hz"),
+        );
+    }
+}
+
+#[test]
+fn polling_rate_rounding() {
+    let settings = dpi(&[(800, BLUE)], 600);
+    assert_message_matches(&settings.message().unwrap(), "polling/500Hz");
+}
+
+#[test]
+fn lighting() {
+    let modes = [
+        (LightingMode::Off, "rgb/off"),
+        (LightingMode::Glorious, "rgb/glorious"),
+        (LightingMode::SeamlessBreathing, "rgb/seamless_breathing"),
+    ];
+
+    for (mode, name) in modes {
+        let settings = LightingSettings::new(mode, 0x14, 0x0C);
+        assert_message_matches(&settings.message().unwrap(), name);
+    }
+}
+
+#[test]
+fn lighting_wireless() {
+    let settings =
+        LightingSettings::new(LightingMode::Glorious, 0x14, 0x0C).with_wireless(0x05, 0x02);
+    let message = settings.message().unwrap();
+    let first = message.reports()[0].data();
+
+    // Wired rate and brightness, number of colours, then wireless rate and brightness
+    assert_eq!(first[6..11], [0x0C, 0x14, 0x07, 0x02, 0x05]);
+    assert_eq!(
+        Operation::decode(message.reports()).unwrap(),
+        Operation::Lighting(settings)
+    );
+}
+
+#[test]
+fn sent_reports() {
+    let recorder = Recorder::new();
+    set_dpi_settings(&recorder, &dpi(&[(850, BLUE), (800, BLUE)], 1000), None).unwrap();
+    assert_reports_match(&recorder.sent(), "dpi/+800");
+}
+
+fn capture_reports(name: &str) -> Vec<Report> {
+    capture(name)
+        .iter()
+        .map(|bytes| Report::from_bytes(bytes).unwrap())
+        .collect()
+}
+
+#[test]
+fn decode() {
+    let decoded = Operation::decode(&capture_reports("dpi/+800")).unwrap();
+    assert_eq!(
+        decoded,
+        Operation::Dpi(dpi(&[(850, BLUE), (800, BLUE)], 1000))
+    );
+
+    let decoded = Operation::decode(&capture_reports("rgb/glorious")).unwrap();
+    let expected = LightingSettings::new(LightingMode::Glorious, 0x14, 0x0C);
+    assert_eq!(decoded, Operation::Lighting(expected));
+}
+
+#[test]
+fn decode_invalid_dpi() {
+    // The DPI of the first stage follows the 5 byte header and 6 bytes of settings.
+    for (dpi, value) in [
+        ([0xFF, 0xFF], 0xFFFF),
+        ([0x09, 0x02], 0x209),
+        ([0x00, 0x00], 0x00),
+    ] {
+        let mut reports = capture("dpi/+800");
+        reports[0][11..13].copy_from_slice(&dpi);
+        let reports: Vec<_> = reports
+            .iter()
+            .map(|bytes| Report::from_bytes(bytes).unwrap())
+            .collect();
+
+        assert!(matches!(
+            Operation::decode(&reports),
+            Err(DecodeError::ValueError { field: "DPI", value: v }) if v == value
+        ));
+    }
+}
+
+#[test]
+fn decode_stream() {
+    let mut reports = capture_reports("rgb/off");
+    reports.extend(capture_reports("polling/250Hz"));
+    reports.extend(
+        Timeout::Minutes(5)
+            .message()
+            .unwrap()
+            .reports()
+            .iter()
+            .cloned(),
+    );
+
+    let decoded: Vec<_> = Decoder::new(&reports).map(Result::unwrap).collect();
+    assert_eq!(
+        decoded,
+        [
+            Operation::Lighting(LightingSettings::new(LightingMode::Off, 0x14, 0x0C)),
+            Operation::Dpi(dpi(&[(800, BLUE)], 250)),
+            Operation::Timeout(Timeout::Minutes(5)),
+        ]
+    );
+}
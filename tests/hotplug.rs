@@ -0,0 +1,46 @@
+//! Checks that the watcher reports devices appearing and disappearing between scans.
+
+use std::collections::VecDeque;
+
+use i2control::device::{
+    discovery::{GLORIOUS_VID, I2_WIRED_PID, I2_WL_PID, KnownDevice, known_device},
+    hotplug::{HotplugEvent, Scanner, Watcher},
+};
+
+/// Returns a fixed sequence of scans.
+struct FakeScanner(VecDeque<Vec<&'static KnownDevice>>);
+
+impl Scanner for FakeScanner {
+    fn scan(&mut self) -> i2control::Result<Vec<&'static KnownDevice>> {
+        Ok(self.0.pop_front().unwrap_or_default())
+    }
+}
+
+#[test]
+fn events() {
+    let wired = known_device(GLORIOUS_VID, I2_WIRED_PID).unwrap();
+    let wireless = known_device(GLORIOUS_VID, I2_WL_PID).unwrap();
+
+    // Each mouse exposes several interfaces, so devices are listed more than once.
+    let scans = [
+        vec![wireless, wireless],
+        vec![wireless, wireless],
+        vec![],
+        vec![wired, wireless, wired],
+    ];
+    let mut watcher = Watcher::new(FakeScanner(scans.into()));
+
+    assert_eq!(watcher.poll().unwrap(), [HotplugEvent::Connected(wireless)]);
+    assert_eq!(watcher.poll().unwrap(), []);
+    assert_eq!(
+        watcher.poll().unwrap(),
+        [HotplugEvent::Disconnected(wireless)]
+    );
+    assert_eq!(
+        watcher.poll().unwrap(),
+        [
+            HotplugEvent::Connected(wired),
+            HotplugEvent::Connected(wireless)
+        ]
+    );
+}
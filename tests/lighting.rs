@@ -0,0 +1,54 @@
+//! Checks custom palettes for lighting modes.
+
+use i2control::{
+    config::LightingConfig,
+    device::{
+        decode::Operation,
+        lighting::{LightingSettings, probe_palette},
+    },
+    error::ValidationError,
+    rgb,
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+const RED: Rgb = rgb!(0xFF0000);
+const GREEN: Rgb = rgb!(0x00FF00);
+const BLUE: Rgb = rgb!(0x0000FF);
+
+#[test]
+fn custom_palette() {
+    let mode = LightingMode::Rave { col: RED };
+    let settings = LightingSettings::new(mode, 0x14, 0x0C).with_colours(vec![GREEN, BLUE]);
+    assert_eq!(settings.mode, LightingMode::Rave { col: GREEN });
+    settings.validate().unwrap();
+
+    let message = settings.message().unwrap();
+    let decoded = Operation::decode(message.reports()).unwrap();
+    assert_eq!(decoded, Operation::Lighting(settings.clone()));
+
+    let config = LightingConfig::from(&settings);
+    assert_eq!(config.colours, Some(vec![GREEN, BLUE]));
+    assert_eq!(config.settings(), settings);
+}
+
+#[test]
+fn custom_palette_count() {
+    let settings = LightingSettings::new(LightingMode::Wave, 0x14, 0x0C).with_colours(vec![RED]);
+    assert!(matches!(
+        settings.validate(),
+        Err(ValidationError::ColourCount {
+            expected: 7,
+            found: 1
+        })
+    ));
+}
+
+#[test]
+fn probe() {
+    let black = Rgb::default();
+    assert_eq!(
+        probe_palette(LightingMode::Tail, 2, RED),
+        [black, black, RED, black, black, black]
+    );
+    assert!(probe_palette(LightingMode::Off, 0, RED).is_empty());
+}
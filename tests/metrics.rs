@@ -0,0 +1,54 @@
+//! Checks the OpenMetrics text exported for a mouse.
+
+use i2control::{
+    device::{
+        battery::BatteryStatus,
+        discovery::{GLORIOUS_VID, I2_WL_PID, known_device},
+        dpi::{DpiSettings, DpiStage},
+    },
+    metrics::Metrics,
+};
+
+#[test]
+fn exposition() {
+    let metrics = Metrics {
+        model: known_device(GLORIOUS_VID, I2_WL_PID),
+        battery: Some(BatteryStatus {
+            percentage: 85,
+            charging: true,
+        }),
+        dpi: Some(DpiSettings {
+            active_stage: 1,
+            stages: vec![DpiStage::new(800), DpiStage::new(1600)],
+            polling_rate: 500,
+            ..Default::default()
+        }),
+    };
+
+    let expected = "\
+# TYPE i2control_up gauge
+# HELP i2control_up Whether every reading from the mouse succeeded.
+i2control_up 1
+# TYPE i2control_device info
+# HELP i2control_device Model and connection mode.
+i2control_device_info{model=\"Model I2 Wireless\",mode=\"wireless\"} 1
+# TYPE i2control_battery_percent gauge
+# HELP i2control_battery_percent Battery level.
+i2control_battery_percent 85
+# TYPE i2control_battery_charging gauge
+# HELP i2control_battery_charging Whether the battery is charging.
+i2control_battery_charging 1
+# TYPE i2control_dpi gauge
+# HELP i2control_dpi DPI of the active stage.
+i2control_dpi 1600
+# TYPE i2control_dpi_stage gauge
+# HELP i2control_dpi_stage Index of the active DPI stage.
+i2control_dpi_stage 1
+# TYPE i2control_polling_rate_hertz gauge
+# UNIT i2control_polling_rate_hertz hertz
+# HELP i2control_polling_rate_hertz Polling rate.
+i2control_polling_rate_hertz 500
+# EOF
+";
+    assert_eq!(metrics.to_string(), expected);
+}
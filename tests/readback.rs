@@ -0,0 +1,202 @@
+//! Checks commands which read settings back from the mouse, using replies queued on a recorder.
+//!
+//! The read requests haven't been captured from Glorious Core yet, so they're only built with the
+//! `unverified-reads` feature.
+#![cfg(feature = "unverified-reads")]
+
+use i2control::{
+    config::{Config, apply_config, get_config},
+    device::{
+        dpi::{self, DpiSettings, DpiStage, DpiUpdate, StageColour, update_dpi},
+        lighting::LightingSettings,
+        message::MessageBuilder,
+        transport::Recorder,
+    },
+    rgb,
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+#[test]
+fn export_round_trip() {
+    let lighting = LightingSettings::new(
+        LightingMode::Breathing {
+            col: rgb!(0xFF0000),
+        },
+        0x14,
+        0x0C,
+    )
+    .with_wireless(0x05, 0x0C);
+    let dpi = DpiSettings {
+        active_stage: 1,
+        stages: vec![
+            DpiStage::new(800),
+            DpiStage {
+                dpi: 1600,
+                colour: rgb!(0x26B4FF),
+            },
+        ],
+        ..Default::default()
+    };
+
+    // The mouse replies to read requests with the messages that would set its current settings.
+    let recorder = Recorder::with_replies(
+        [
+            lighting.message().unwrap().to_bytes(),
+            dpi.message().unwrap().to_bytes(),
+        ]
+        .concat(),
+    );
+
+    let config = get_config(&recorder).unwrap();
+    let toml = config.to_toml().unwrap();
+    let parsed: Config = toml.parse().unwrap();
+    assert_eq!(parsed, config);
+
+    recorder.take_sent();
+    apply_config(&recorder, &parsed, None).unwrap();
+    let expected = [
+        lighting.message().unwrap().to_bytes(),
+        dpi.message().unwrap().to_bytes(),
+    ]
+    .concat();
+    assert_eq!(recorder.sent(), expected);
+}
+
+/// Stage colours set by Glorious Core which haven't been captured are kept as they were read.
+#[test]
+fn export_uncaptured_colour() {
+    let dpi = DpiSettings {
+        stages: vec![DpiStage {
+            dpi: 800,
+            colour: rgb!(0xFF0000),
+        }],
+        ..Default::default()
+    };
+    let recorder = Recorder::with_replies(
+        [
+            LightingSettings::new(LightingMode::Off, 0, 1)
+                .message()
+                .unwrap()
+                .to_bytes(),
+            dpi.message().unwrap().to_bytes(),
+        ]
+        .concat(),
+    );
+
+    let config = get_config(&recorder).unwrap();
+    let toml = config.to_toml().unwrap();
+    assert!(toml.contains("colours = [\"#FF0000\"]"), "{toml}");
+
+    // The colour is checked against the current settings, which still have it.
+    let parsed: Config = toml.parse().unwrap();
+    recorder.take_sent();
+    dpi.message()
+        .unwrap()
+        .to_bytes()
+        .into_iter()
+        .for_each(|report| recorder.push_reply(report));
+    apply_config(&recorder, &parsed, None).unwrap();
+    assert!(
+        recorder
+            .sent()
+            .ends_with(&dpi.message().unwrap().to_bytes())
+    );
+
+    // Unless the stage's colour was changed since.
+    let changed = DpiSettings {
+        stages: vec![DpiStage::new(800)],
+        ..Default::default()
+    };
+    let recorder = Recorder::with_replies(changed.message().unwrap().to_bytes());
+    assert!(apply_config(&recorder, &parsed, None).is_err());
+    assert_eq!(recorder.sent().len(), dpi::NUM_REPORTS as usize); // Only the read request
+}
+
+#[test]
+fn update_dpi_keeps_current() {
+    let current = DpiSettings {
+        active_stage: 1,
+        stages: vec![DpiStage::new(800), DpiStage::new(1600)],
+        polling_rate: 500,
+        ..Default::default()
+    };
+    let recorder = Recorder::with_replies(current.message().unwrap().to_bytes());
+
+    let update = DpiUpdate {
+        stage_dpi: Some((0, 400)),
+        stage_colour: Some((1, StageColour::Blue)),
+        ..Default::default()
+    };
+    let settings = update_dpi(&recorder, &update, None).unwrap();
+
+    let mut expected = current;
+    expected.stages[0].dpi = 400;
+    expected.stages[1].colour = StageColour::Blue.rgb();
+    assert_eq!(settings, expected);
+
+    let request = MessageBuilder::read_request(dpi::OPERATION_ID, dpi::NUM_REPORTS)
+        .build()
+        .unwrap();
+    assert_eq!(
+        recorder.sent(),
+        [request.to_bytes(), expected.message().unwrap().to_bytes()].concat()
+    );
+}
+
+/// Replacing the stages keeps the colours read back for stages which still exist.
+#[test]
+fn update_dpi_keeps_colours() {
+    let blue = StageColour::Blue.rgb();
+    let current = DpiSettings {
+        stages: vec![
+            DpiStage {
+                dpi: 400,
+                colour: blue,
+            },
+            DpiStage {
+                dpi: 800,
+                colour: rgb!(0xFF0000),
+            },
+        ],
+        ..Default::default()
+    };
+    let recorder = Recorder::with_replies(current.message().unwrap().to_bytes());
+
+    let update = DpiUpdate {
+        stages: Some(vec![800, 1600, 3200]),
+        ..Default::default()
+    };
+    let settings = update_dpi(&recorder, &update, None).unwrap();
+    assert_eq!(
+        settings.stages,
+        [
+            DpiStage {
+                dpi: 800,
+                colour: blue,
+            },
+            DpiStage {
+                dpi: 1600,
+                colour: rgb!(0xFF0000),
+            },
+            DpiStage::new(3200),
+        ]
+    );
+}
+
+#[test]
+fn update_dpi_missing_stage() {
+    let current = DpiSettings {
+        stages: vec![DpiStage::new(800)],
+        ..Default::default()
+    };
+    let recorder = Recorder::with_replies(current.message().unwrap().to_bytes());
+
+    let update = DpiUpdate {
+        stage_dpi: Some((1, 400)),
+        ..Default::default()
+    };
+    assert!(update_dpi(&recorder, &update, None).is_err());
+
+    // Only the read request was sent.
+    assert_eq!(recorder.sent().len(), dpi::NUM_REPORTS as usize);
+}
@@ -0,0 +1,249 @@
+//! Checks the daemon's responses, and what is sent to the mouse for each request.
+
+use std::sync::{Arc, Mutex};
+
+use hidapi::HidError;
+use serde_json::{Value, json};
+
+use i2control::{
+    Error,
+    config::{Config, apply_config},
+    device::{
+        discovery::DiscoveryError,
+        transport::{Recorder, Transport},
+    },
+    rpc::{
+        DEVICE_ERROR, Device, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR,
+        Request, RpcError, handle, respond, response,
+    },
+};
+
+const LIGHTING: &str = r#"{ "lighting": { "mode": "off", "brightness": 0, "rate": 1 } }"#;
+
+/// A mouse which records what it's sent, or fails every report if it's been unplugged.
+struct Mouse {
+    recorder: Arc<Recorder>,
+    unplugged: bool,
+}
+
+impl Mouse {
+    fn check(&self) -> i2control::Result<()> {
+        if self.unplugged {
+            return Err(Error::Transport(HidError::HidApiError {
+                message: "No such device".to_string(),
+            }));
+        }
+
+        Ok(())
+    }
+}
+
+impl Transport for Mouse {
+    fn send_feature_report(&self, data: &[u8]) -> i2control::Result<()> {
+        self.check()?;
+        self.recorder.send_feature_report(data)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> i2control::Result<usize> {
+        self.check()?;
+        self.recorder.get_feature_report(buf)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout_ms: i32) -> i2control::Result<usize> {
+        self.check()?;
+        self.recorder.read_timeout(buf, timeout_ms)
+    }
+
+    fn report_interval(&self) -> std::time::Duration {
+        self.recorder.report_interval()
+    }
+}
+
+/// Returns a device which connects to each mouse in turn, and then finds no more mice.
+fn device(
+    mice: Vec<Mouse>,
+) -> Mutex<Device<Mouse, impl FnMut() -> i2control::Result<Mouse> + use<>>> {
+    let mut mice = mice.into_iter();
+    Mutex::new(Device::new(move || {
+        mice.next()
+            .ok_or(Error::DeviceNotFound(DiscoveryError::NoDevices))
+    }))
+}
+
+fn mouse(recorder: &Arc<Recorder>) -> Mouse {
+    Mouse {
+        recorder: Arc::clone(recorder),
+        unplugged: false,
+    }
+}
+
+fn unplugged() -> Mouse {
+    Mouse {
+        recorder: Arc::new(Recorder::new()),
+        unplugged: true,
+    }
+}
+
+/// Returns what applying the config sends to the mouse.
+fn applied(config: &str) -> Vec<Vec<u8>> {
+    let config: Config = serde_json::from_str(config).unwrap();
+    let recorder = Recorder::new();
+    apply_config(&recorder, &config, None).unwrap();
+    recorder.sent()
+}
+
+fn request(id: Value, method: &str, params: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": serde_json::from_str::<Value>(params).unwrap(),
+    })
+    .to_string()
+}
+
+fn error_code(response: Option<Value>) -> Value {
+    response.unwrap()["error"]["code"].clone()
+}
+
+#[test]
+fn apply() {
+    let recorder = Arc::new(Recorder::new());
+    let device = device(vec![mouse(&recorder)]);
+
+    assert_eq!(
+        respond(&device, &request(json!(1), "apply", LIGHTING)),
+        Some(json!({ "jsonrpc": "2.0", "id": 1, "result": null }))
+    );
+    assert_eq!(recorder.take_sent(), applied(LIGHTING));
+
+    // The connection is kept open for the next request.
+    let request: Request = serde_json::from_str(&request(json!("a"), "apply", LIGHTING)).unwrap();
+    assert!(matches!(handle(&device, request), Ok(Value::Null)));
+    assert_eq!(recorder.sent(), applied(LIGHTING));
+}
+
+#[test]
+fn responses() {
+    assert_eq!(
+        response(json!(1), Ok(json!(85))),
+        json!({ "jsonrpc": "2.0", "id": 1, "result": 85 })
+    );
+    assert_eq!(
+        response(json!("a"), Err(RpcError::new(METHOD_NOT_FOUND, "Unknown"))),
+        json!({
+            "jsonrpc": "2.0",
+            "id": "a",
+            "error": { "code": METHOD_NOT_FOUND, "message": "Unknown" },
+        })
+    );
+}
+
+/// Notifications are run, but get no response even if they fail.
+#[test]
+fn notifications() {
+    let recorder = Arc::new(Recorder::new());
+    let device = device(vec![mouse(&recorder)]);
+
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "apply",
+        "params": serde_json::from_str::<Value>(LIGHTING).unwrap(),
+    });
+    assert_eq!(respond(&device, &notification.to_string()), None);
+    assert_eq!(recorder.sent(), applied(LIGHTING));
+
+    let notification = json!({ "jsonrpc": "2.0", "method": "unknown" });
+    assert_eq!(respond(&device, &notification.to_string()), None);
+}
+
+/// When the mouse has been unplugged and plugged back in, it's reopened and the request retried.
+#[test]
+fn reconnect() {
+    let recorder = Arc::new(Recorder::new());
+    let device = device(vec![unplugged(), mouse(&recorder)]);
+
+    assert_eq!(
+        respond(&device, &request(json!(1), "apply", LIGHTING)),
+        Some(json!({ "jsonrpc": "2.0", "id": 1, "result": null }))
+    );
+    assert_eq!(recorder.sent(), applied(LIGHTING));
+}
+
+/// Requests are only retried once.
+#[test]
+fn reconnect_failed() {
+    let device = device(vec![unplugged(), unplugged(), unplugged()]);
+    let response = respond(&device, &request(json!(1), "apply", LIGHTING));
+    assert_eq!(error_code(response), DEVICE_ERROR);
+
+    // The third mouse is only opened by the next request.
+    let response = respond(&device, &request(json!(2), "apply", LIGHTING));
+    assert_eq!(error_code(response), DEVICE_ERROR);
+    let response = respond(&device, &request(json!(3), "apply", LIGHTING));
+    assert!(
+        response.unwrap()["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains(&DiscoveryError::NoDevices.to_string())
+    );
+}
+
+#[test]
+fn errors() {
+    let recorder = Arc::new(Recorder::new());
+    let device = device(vec![mouse(&recorder)]);
+
+    let response = respond(&device, "{");
+    assert_eq!(response.as_ref().unwrap()["id"], Value::Null);
+    assert_eq!(error_code(response), PARSE_ERROR);
+
+    let response = respond(&device, r#"{ "jsonrpc": "2.0", "id": 1 }"#);
+    assert_eq!(error_code(response), INVALID_REQUEST);
+
+    let response = respond(
+        &device,
+        r#"{ "jsonrpc": "1.0", "id": 1, "method": "apply" }"#,
+    );
+    assert_eq!(error_code(response), INVALID_REQUEST);
+
+    let response = respond(&device, &request(json!(1), "unknown", "null"));
+    assert_eq!(response.as_ref().unwrap()["id"], 1);
+    assert_eq!(error_code(response), METHOD_NOT_FOUND);
+
+    // Params which aren't a config, or settings which are out of range
+    for params in [
+        r#"{ "timeout": "never" }"#,
+        r#"{ "lighting": { "mode": "off", "brightness": 0, "rate": 0 } }"#,
+    ] {
+        let response = respond(&device, &request(json!(1), "apply", params));
+        assert_eq!(error_code(response), INVALID_PARAMS);
+    }
+    assert!(recorder.sent().is_empty());
+}
+
+#[test]
+fn no_mouse() {
+    let device = device(Vec::new());
+    let response = respond(&device, &request(json!(1), "apply", LIGHTING));
+    assert_eq!(error_code(response), DEVICE_ERROR);
+}
+
+#[test]
+fn poisoned() {
+    let recorder = Arc::new(Recorder::new());
+    let device = device(vec![mouse(&recorder)]);
+    let _ = std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let _device = device.lock().unwrap();
+                panic!("Request panicked");
+            })
+            .join()
+    });
+    assert!(device.is_poisoned());
+
+    let response = respond(&device, &request(json!(1), "apply", LIGHTING));
+    assert_eq!(response.unwrap()["result"], Value::Null);
+    assert_eq!(recorder.sent(), applied(LIGHTING));
+}
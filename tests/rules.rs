@@ -0,0 +1,78 @@
+//! Checks which lighting rules choose, and when changes are sent.
+
+use std::time::Duration;
+
+use i2control::{
+    config::Config,
+    rgb,
+    rules::{Debouncer, Readings},
+    util::{lighting_mode::LightingMode, rgb::Rgb},
+};
+
+#[test]
+fn evaluate() {
+    let config: Config = r#"
+        [[rules.when]]
+        signal = "cpu"
+        above = 80
+        colour = "red"
+        effect = "breathing"
+
+        [[rules.when]]
+        signal = "battery"
+        below = 20
+        colour = "orange"
+
+        [[rules.when]]
+        signal = "battery"
+        colour = "green"
+    "#
+    .parse()
+    .unwrap();
+    let rules = config.rules.unwrap();
+
+    let readings = |battery, cpu| Readings {
+        battery: Some(battery),
+        cpu,
+        dpi_stage: None,
+    };
+
+    assert_eq!(
+        rules.evaluate(&readings(10.0, Some(90.0))),
+        Some(LightingMode::BreathingSingleColour {
+            col: rgb!(0xFF0000)
+        })
+    );
+    assert_eq!(
+        rules.evaluate(&readings(10.0, None)),
+        Some(LightingMode::SingleColour {
+            col: rgb!(0xFFA500)
+        })
+    );
+    assert_eq!(
+        rules.evaluate(&readings(50.0, Some(20.0))),
+        Some(LightingMode::SingleColour {
+            col: rgb!(0x008000)
+        })
+    );
+    assert_eq!(rules.evaluate(&Readings::default()), None);
+}
+
+#[test]
+fn debounce() {
+    let secs = Duration::from_secs;
+    let mut debouncer = Debouncer::new(secs(10));
+
+    // The first value is sent immediately, and repeats aren't sent.
+    assert_eq!(debouncer.update("green", secs(0)), Some("green"));
+    assert_eq!(debouncer.update("green", secs(5)), None);
+
+    // Changes which don't last for the delay are dropped.
+    assert_eq!(debouncer.update("orange", secs(10)), None);
+    assert_eq!(debouncer.update("green", secs(15)), None);
+
+    assert_eq!(debouncer.update("orange", secs(20)), None);
+    assert_eq!(debouncer.update("orange", secs(25)), None);
+    assert_eq!(debouncer.update("orange", secs(30)), Some("orange"));
+    assert_eq!(debouncer.update("orange", secs(35)), None);
+}